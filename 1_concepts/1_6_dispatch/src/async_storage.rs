@@ -0,0 +1,293 @@
+use std::future::{self, Future};
+use std::pin::Pin;
+use std::sync::Mutex;
+
+use crate::{rules, TryRepositoryError, TryStorage, User};
+
+// Boxed future returned by AsyncStorage methods, so the trait stays object safe
+//
+// It's `Send`, so the operations can run on multi-threaded executors.
+pub type StorageFuture<'a, T, E> = Pin<Box<dyn Future<Output = Result<T, E>> + Send + 'a>>;
+
+// Asynchronous counterpart of the TryStorage trait
+//
+// Reads return owned values, as borrowing from the storage across `.await`
+// points isn't possible for most backends. Storages are `Send` and `Sync`, so
+// the futures of the repositories borrowing them are `Send` as well.
+pub trait AsyncStorage<K, V>: Send + Sync {
+    type Error;
+
+    fn set(&mut self, key: K, val: V) -> StorageFuture<'_, (), Self::Error>;
+    fn get<'a>(&'a self, key: &'a K) -> StorageFuture<'a, Option<V>, Self::Error>;
+    fn remove<'a>(&'a mut self, key: &'a K) -> StorageFuture<'a, Option<V>, Self::Error>;
+    // Lists all the stored entries in no particular order.
+    fn entries(&self) -> StorageFuture<'_, Vec<(K, V)>, Self::Error>;
+}
+
+// Adapter making any TryStorage usable as an AsyncStorage
+//
+// The wrapped operations are blocking and run right away, the returned
+// futures are always ready. The storage is behind a `Mutex`, so it only has to
+// be `Send` for the adapter to be `Sync`.
+pub struct AsyncAdapter<S>(Mutex<S>);
+
+impl<S> AsyncAdapter<S> {
+    pub fn new(storage: S) -> Self {
+        Self(Mutex::new(storage))
+    }
+
+    pub fn into_inner(self) -> S {
+        self.0.into_inner().unwrap()
+    }
+}
+
+impl<K, V, S> AsyncStorage<K, V> for AsyncAdapter<S>
+where
+    K: Send + 'static,
+    V: Clone + Send + 'static,
+    S: TryStorage<K, V> + Send,
+    S::Error: Send + 'static,
+{
+    type Error = S::Error;
+
+    fn set(&mut self, key: K, val: V) -> StorageFuture<'_, (), Self::Error> {
+        let res = self.0.get_mut().unwrap().try_set(key, val);
+        Box::pin(future::ready(res))
+    }
+
+    fn get<'a>(&'a self, key: &'a K) -> StorageFuture<'a, Option<V>, Self::Error> {
+        let storage = self.0.lock().unwrap();
+        let val = storage.try_get(key).map(|v| v.map(|v| v.into_owned()));
+        Box::pin(future::ready(val))
+    }
+
+    fn remove<'a>(&'a mut self, key: &'a K) -> StorageFuture<'a, Option<V>, Self::Error> {
+        let res = self.0.get_mut().unwrap().try_remove(key);
+        Box::pin(future::ready(res))
+    }
+
+    fn entries(&self) -> StorageFuture<'_, Vec<(K, V)>, Self::Error> {
+        let storage = self.0.lock().unwrap();
+        let entries = storage
+            .try_iter()
+            .map(|iter| iter.map(|(k, v)| (k, v.into_owned())).collect());
+        Box::pin(future::ready(entries))
+    }
+}
+
+// Async counterpart of the repository logic shared by both dispatch flavours
+mod ops {
+    use super::*;
+
+    type OpResult<T, S> = Result<T, TryRepositoryError<<S as AsyncStorage<u64, User>>::Error>>;
+
+    pub async fn add_user<S>(storage: &mut S, user: User) -> OpResult<(), S>
+    where
+        S: AsyncStorage<u64, User> + ?Sized,
+    {
+        rules::check_new(user.id, stored(storage, user.id).await?)?;
+        rules::check_email(users(storage).await?, &user)?;
+        let res = storage.set(user.id, user).await;
+        res.map_err(TryRepositoryError::Storage)
+    }
+
+    pub async fn update_user<S>(storage: &mut S, user: User) -> OpResult<(), S>
+    where
+        S: AsyncStorage<u64, User> + ?Sized,
+    {
+        rules::check_existing(user.id, stored(storage, user.id).await?)?;
+        rules::check_email(users(storage).await?, &user)?;
+        let res = storage.set(user.id, user).await;
+        res.map_err(TryRepositoryError::Storage)
+    }
+
+    pub async fn find_by_email<S>(storage: &S, email: &str) -> Result<Option<User>, S::Error>
+    where
+        S: AsyncStorage<u64, User> + ?Sized,
+    {
+        let entries = storage.entries().await?;
+        Ok(rules::find_by_email(
+            entries.into_iter().map(|(_, user)| user),
+            email,
+        ))
+    }
+
+    async fn stored<S>(storage: &S, id: u64) -> OpResult<bool, S>
+    where
+        S: AsyncStorage<u64, User> + ?Sized,
+    {
+        let user = storage.get(&id).await;
+        Ok(user.map_err(TryRepositoryError::Storage)?.is_some())
+    }
+
+    async fn users<S>(storage: &S) -> OpResult<impl Iterator<Item = User>, S>
+    where
+        S: AsyncStorage<u64, User> + ?Sized,
+    {
+        let entries = storage.entries().await;
+        Ok(entries
+            .map_err(TryRepositoryError::Storage)?
+            .into_iter()
+            .map(|(_, user)| user))
+    }
+}
+
+// Dynamic Dispatch: async UserRepository with trait objects
+pub struct AsyncUserRepositoryDyn<E> {
+    storage: Box<dyn AsyncStorage<u64, User, Error = E>>,
+}
+
+impl<E> AsyncUserRepositoryDyn<E> {
+    pub fn new(storage: Box<dyn AsyncStorage<u64, User, Error = E>>) -> Self {
+        Self { storage }
+    }
+
+    pub async fn add_user(&mut self, user: User) -> Result<(), TryRepositoryError<E>> {
+        ops::add_user(&mut *self.storage, user).await
+    }
+
+    pub async fn get_user(&self, id: u64) -> Result<Option<User>, E> {
+        self.storage.get(&id).await
+    }
+
+    pub async fn update_user(&mut self, user: User) -> Result<(), TryRepositoryError<E>> {
+        ops::update_user(&mut *self.storage, user).await
+    }
+
+    pub async fn remove_user(&mut self, id: u64) -> Result<Option<User>, E> {
+        self.storage.remove(&id).await
+    }
+
+    pub async fn find_by_email(&self, email: &str) -> Result<Option<User>, E> {
+        ops::find_by_email(&*self.storage, email).await
+    }
+}
+
+// Static Dispatch: async UserRepository with generic storage
+pub struct AsyncUserRepositoryStatic<S>
+where
+    S: AsyncStorage<u64, User>,
+{
+    storage: S,
+}
+
+impl<S> AsyncUserRepositoryStatic<S>
+where
+    S: AsyncStorage<u64, User>,
+{
+    pub fn new(storage: S) -> Self {
+        Self { storage }
+    }
+
+    pub async fn add_user(&mut self, user: User) -> Result<(), TryRepositoryError<S::Error>> {
+        ops::add_user(&mut self.storage, user).await
+    }
+
+    pub async fn get_user(&self, id: u64) -> Result<Option<User>, S::Error> {
+        self.storage.get(&id).await
+    }
+
+    pub async fn update_user(&mut self, user: User) -> Result<(), TryRepositoryError<S::Error>> {
+        ops::update_user(&mut self.storage, user).await
+    }
+
+    pub async fn remove_user(&mut self, id: u64) -> Result<Option<User>, S::Error> {
+        self.storage.remove(&id).await
+    }
+
+    pub async fn find_by_email(&self, email: &str) -> Result<Option<User>, S::Error> {
+        ops::find_by_email(&self.storage, email).await
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+
+    use super::*;
+    use crate::{HashMapStorage, RepositoryError, SqliteStorage, TryAdapter};
+
+    fn user(id: u64) -> User {
+        User {
+            id,
+            email: format!("user{id}@example.com").into(),
+            activated: true,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dynamic_dispatch() {
+        let storage = AsyncAdapter::new(TryAdapter(HashMapStorage::new()));
+        let mut repo: AsyncUserRepositoryDyn<Infallible> =
+            AsyncUserRepositoryDyn::new(Box::new(storage));
+
+        repo.add_user(user(1)).await.unwrap();
+        assert_eq!(repo.get_user(1).await.unwrap(), Some(user(1)));
+
+        repo.remove_user(1).await.unwrap();
+        assert_eq!(repo.get_user(1).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_static_dispatch() {
+        let storage = AsyncAdapter::new(SqliteStorage::open_in_memory().unwrap());
+        let mut repo = AsyncUserRepositoryStatic::new(storage);
+
+        repo.add_user(user(2)).await.unwrap();
+        assert_eq!(repo.get_user(2).await.unwrap(), Some(user(2)));
+        assert_eq!(
+            repo.find_by_email("USER2@example.com").await.unwrap(),
+            Some(user(2))
+        );
+        assert!(matches!(
+            repo.add_user(user(u64::MAX)).await,
+            Err(TryRepositoryError::Storage(_))
+        ));
+
+        repo.remove_user(2).await.unwrap();
+        assert_eq!(repo.get_user(2).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_futures_are_send() {
+        let storage = AsyncAdapter::new(SqliteStorage::open_in_memory().unwrap());
+        let mut repo: AsyncUserRepositoryDyn<rusqlite::Error> =
+            AsyncUserRepositoryDyn::new(Box::new(storage));
+
+        let repo = tokio::spawn(async move {
+            repo.add_user(user(1)).await.unwrap();
+            repo
+        })
+        .await
+        .unwrap();
+        assert_eq!(repo.get_user(1).await.unwrap(), Some(user(1)));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_duplicates() {
+        let storage = AsyncAdapter::new(TryAdapter(HashMapStorage::new()));
+        let mut repo: AsyncUserRepositoryDyn<Infallible> =
+            AsyncUserRepositoryDyn::new(Box::new(storage));
+        repo.add_user(user(1)).await.unwrap();
+
+        let mut duplicate = user(1);
+        duplicate.email = "other@example.com".into();
+        assert_eq!(
+            repo.add_user(duplicate).await,
+            Err(RepositoryError::AlreadyExists(1).into())
+        );
+        assert_eq!(repo.get_user(1).await.unwrap(), Some(user(1)));
+        assert_eq!(
+            repo.update_user(user(2)).await,
+            Err(RepositoryError::NotFound(2).into())
+        );
+
+        let mut taken = user(2);
+        taken.email = "USER1@example.com".into();
+        assert_eq!(
+            repo.add_user(taken).await,
+            Err(RepositoryError::EmailTaken("USER1@example.com".into()).into())
+        );
+    }
+}
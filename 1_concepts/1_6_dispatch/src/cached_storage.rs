@@ -0,0 +1,187 @@
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+use crate::Storage;
+
+// Least recently used cache with a fixed capacity
+struct Lru<K, V> {
+    capacity: usize,
+    tick: u64,
+    entries: HashMap<K, (V, u64)>,
+    // Keys of `entries`, ordered from the least recently used one.
+    order: BTreeMap<u64, K>,
+}
+
+impl<K: Clone + Eq + Hash, V> Lru<K, V> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+        }
+    }
+
+    fn get(&mut self, key: &K) -> Option<&V> {
+        let (val, used) = self.entries.get_mut(key)?;
+        self.tick += 1;
+        self.order.remove(used);
+        self.order.insert(self.tick, key.clone());
+        *used = self.tick;
+        Some(val)
+    }
+
+    fn insert(&mut self, key: K, val: V) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.remove(&key);
+        if self.entries.len() == self.capacity {
+            if let Some((_, oldest)) = self.order.pop_first() {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.tick += 1;
+        self.order.insert(self.tick, key.clone());
+        self.entries.insert(key, (val, self.tick));
+    }
+
+    fn remove(&mut self, key: &K) {
+        if let Some((_, used)) = self.entries.remove(key) {
+            self.order.remove(&used);
+        }
+    }
+}
+
+// Storage decorator keeping the most recently read values in memory, so the
+// wrapped (potentially slow) storage is hit only on cache misses.
+//
+// Writes go straight to the wrapped storage and invalidate the cached value.
+pub struct CachedStorage<K, V, S> {
+    inner: S,
+    cache: RefCell<Lru<K, V>>,
+}
+
+impl<K, V, S> CachedStorage<K, V, S>
+where
+    K: Clone + Eq + Hash,
+{
+    pub fn new(inner: S, capacity: usize) -> Self {
+        Self {
+            inner,
+            cache: RefCell::new(Lru::new(capacity)),
+        }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<K, V, S> Storage<K, V> for CachedStorage<K, V, S>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+    S: Storage<K, V>,
+{
+    fn set(&mut self, key: K, val: V) {
+        self.cache.get_mut().remove(&key);
+        self.inner.set(key, val);
+    }
+
+    fn get(&self, key: &K) -> Option<Cow<'_, V>> {
+        if let Some(val) = self.cache.borrow_mut().get(key) {
+            return Some(Cow::Owned(val.clone()));
+        }
+        let val = self.inner.get(key)?;
+        self.cache
+            .borrow_mut()
+            .insert(key.clone(), val.clone().into_owned());
+        Some(val)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        self.cache.get_mut().remove(key);
+        self.inner.remove(key)
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (K, Cow<'_, V>)> + '_> {
+        self.inner.iter()
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{HashMapStorage, MetricsStorage, User, UserRepositoryDyn, UserRepositoryStatic};
+
+    fn user(id: u64) -> User {
+        User {
+            id,
+            email: format!("user{id}@example.com").into(),
+            activated: true,
+        }
+    }
+
+    #[test]
+    fn test_serves_reads_from_cache() {
+        let backend = MetricsStorage::new(HashMapStorage::new());
+        let metrics = backend.metrics();
+        let mut repo = UserRepositoryStatic::new(CachedStorage::new(backend, 2));
+
+        repo.add_user(user(1)).unwrap();
+        repo.add_user(user(2)).unwrap();
+        let misses = metrics.get().misses;
+        let hits = metrics.get().hits;
+
+        for _ in 0..3 {
+            assert_eq!(repo.get_user(1).as_deref(), Some(&user(1)));
+        }
+        assert_eq!(metrics.get().hits, hits + 1);
+        assert_eq!(metrics.get().misses, misses);
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let backend = MetricsStorage::new(HashMapStorage::new());
+        let metrics = backend.metrics();
+        let mut storage = CachedStorage::new(backend, 2);
+        for id in 1..=3 {
+            storage.set(id, user(id));
+        }
+
+        storage.get(&1);
+        storage.get(&2);
+        storage.get(&1);
+        storage.get(&3); // evicts 2
+        let hits = metrics.get().hits;
+
+        storage.get(&1);
+        storage.get(&3);
+        assert_eq!(metrics.get().hits, hits);
+        storage.get(&2);
+        assert_eq!(metrics.get().hits, hits + 1);
+    }
+
+    #[test]
+    fn test_writes_invalidate_cache() {
+        let storage = CachedStorage::new(HashMapStorage::new(), 8);
+        let mut repo = UserRepositoryDyn::new(Box::new(storage));
+
+        repo.add_user(user(1)).unwrap();
+        assert_eq!(repo.get_user(1).as_deref(), Some(&user(1)));
+
+        let updated = User {
+            activated: false,
+            ..user(1)
+        };
+        repo.update_user(updated.clone()).unwrap();
+        assert_eq!(repo.get_user(1).as_deref(), Some(&updated));
+
+        repo.remove_user(1);
+        assert_eq!(repo.get_user(1), None);
+    }
+}
@@ -0,0 +1,431 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::hash::Hash;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{Storage, TryStorage, TryStorageIter};
+
+// Every record on disk is laid out as `[len: u32 LE][crc32: u32 LE][payload]`,
+// where `payload` is a JSON-encoded `Record` of exactly `len` bytes.
+const HEADER_LEN: usize = 8;
+
+// Compaction is not worth it for tiny logs.
+const DEFAULT_COMPACT_THRESHOLD: usize = 1024;
+
+// Log record as it is read back from disk
+#[derive(Deserialize)]
+enum Record<K, V> {
+    Set(K, V),
+    Remove(K),
+}
+
+// Borrowing counterpart of `Record`, so writes don't need to clone
+#[derive(Serialize)]
+enum RecordRef<'a, K, V> {
+    Set(&'a K, &'a V),
+    Remove(&'a K),
+}
+
+// Implementation of Storage trait persisting an append-only log on disk.
+//
+// All entries are kept in memory, the log is only read on `open` to rebuild
+// them. A torn or corrupted tail (e.g. after a crash mid-write) fails its
+// checksum and is truncated away, so the storage always recovers to the last
+// fully written record.
+pub struct FileStorage<K, V> {
+    path: PathBuf,
+    file: File,
+    // Length of the fully written records in the log.
+    len: u64,
+    // Whether a failed append may have left a partial record past `len`.
+    torn: bool,
+    inner: HashMap<K, V>,
+    records: usize,
+    compact_threshold: usize,
+}
+
+impl<K, V> FileStorage<K, V>
+where
+    K: Eq + Hash + Serialize + DeserializeOwned,
+    V: Serialize + DeserializeOwned,
+{
+    // Opens the log at `path`, creating it if missing, and replays it.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&path)?;
+
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf)?;
+
+        let mut inner = HashMap::new();
+        let mut records = 0;
+        let mut offset = 0;
+        while let Some((record, len)) = decode::<K, V>(&buf[offset..]) {
+            match record {
+                Record::Set(key, val) => {
+                    inner.insert(key, val);
+                }
+                Record::Remove(key) => {
+                    inner.remove(&key);
+                }
+            }
+            records += 1;
+            offset += len;
+        }
+        if offset < buf.len() {
+            file.set_len(offset as u64)?;
+            file.sync_all()?;
+        }
+
+        Ok(Self {
+            path,
+            file,
+            len: offset as u64,
+            torn: false,
+            inner,
+            records,
+            compact_threshold: DEFAULT_COMPACT_THRESHOLD,
+        })
+    }
+
+    // Sets the number of log records after which compaction is considered.
+    pub fn with_compact_threshold(mut self, records: usize) -> Self {
+        self.compact_threshold = records;
+        self
+    }
+
+    // Rewrites the log so it contains only the live entries.
+    //
+    // The new log is written next to the old one and atomically renamed over
+    // it, so a crash during compaction leaves the original log intact. The
+    // new log is kept open across the rename, so a failure never leaves the
+    // storage appending to the replaced one.
+    pub fn compact(&mut self) -> io::Result<()> {
+        let tmp_path = self.path.with_extension("compact");
+        let mut tmp = File::create(&tmp_path)?;
+        let mut buf = Vec::new();
+        for (key, val) in &self.inner {
+            encode(&RecordRef::Set(key, val), &mut buf)?;
+        }
+        tmp.write_all(&buf)?;
+        tmp.sync_all()?;
+        let file = OpenOptions::new().read(true).append(true).open(&tmp_path)?;
+        drop(tmp);
+
+        fs::rename(&tmp_path, &self.path)?;
+        self.file = file;
+        self.len = buf.len() as u64;
+        self.torn = false;
+        self.records = self.inner.len();
+        sync_parent_dir(&self.path)
+    }
+
+    // Appends the `record` durably, or leaves the log as it was.
+    //
+    // A partial record is truncated away right after a failure, or before the
+    // next append if truncating fails too, so it never hides the records
+    // following it from `open`.
+    fn append(&mut self, record: &RecordRef<'_, K, V>) -> io::Result<()> {
+        let mut buf = Vec::new();
+        encode(record, &mut buf)?;
+        if self.torn {
+            self.file.set_len(self.len)?;
+            self.torn = false;
+        }
+        let res = self
+            .file
+            .write_all(&buf)
+            .and_then(|()| self.file.sync_data());
+        if let Err(e) = res {
+            self.torn = self.file.set_len(self.len).is_err();
+            return Err(e);
+        }
+        self.len += buf.len() as u64;
+        self.records += 1;
+        Ok(())
+    }
+
+    // Compacts the log if it's worth it.
+    //
+    // A failure isn't an error of the write triggering the compaction, as
+    // that write is durable already, so it's logged and retried on the next
+    // write.
+    fn maybe_compact(&mut self) {
+        if self.records >= self.compact_threshold && self.records > 2 * self.inner.len() {
+            if let Err(e) = self.compact() {
+                log::warn!("failed to compact {}: {e}", self.path.display());
+            }
+        }
+    }
+}
+
+impl<K, V> TryStorage<K, V> for FileStorage<K, V>
+where
+    K: Clone + Eq + Hash + Serialize + DeserializeOwned,
+    V: Clone + Serialize + DeserializeOwned,
+{
+    type Error = io::Error;
+
+    fn try_set(&mut self, key: K, val: V) -> io::Result<()> {
+        self.append(&RecordRef::Set(&key, &val))?;
+        self.inner.insert(key, val);
+        self.maybe_compact();
+        Ok(())
+    }
+
+    fn try_get(&self, key: &K) -> io::Result<Option<Cow<'_, V>>> {
+        Ok(self.inner.get(key).map(Cow::Borrowed))
+    }
+
+    fn try_remove(&mut self, key: &K) -> io::Result<Option<V>> {
+        if !self.inner.contains_key(key) {
+            return Ok(None);
+        }
+        self.append(&RecordRef::Remove(key))?;
+        let val = self.inner.remove(key);
+        self.maybe_compact();
+        Ok(val)
+    }
+
+    fn try_iter(&self) -> io::Result<TryStorageIter<'_, K, V>> {
+        Ok(Storage::iter(self))
+    }
+}
+
+// Panics on I/O errors, use `TryStorage` to handle them instead.
+impl<K, V> Storage<K, V> for FileStorage<K, V>
+where
+    K: Clone + Eq + Hash + Serialize + DeserializeOwned,
+    V: Clone + Serialize + DeserializeOwned,
+{
+    fn set(&mut self, key: K, val: V) {
+        self.try_set(key, val).expect("failed to write storage log");
+    }
+
+    fn get(&self, key: &K) -> Option<Cow<'_, V>> {
+        self.inner.get(key).map(Cow::Borrowed)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        self.try_remove(key).expect("failed to write storage log")
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (K, Cow<'_, V>)> + '_> {
+        Box::new(
+            self.inner
+                .iter()
+                .map(|(k, v)| (k.clone(), Cow::Borrowed(v))),
+        )
+    }
+}
+
+// Makes a rename or a creation of the file at `path` durable.
+fn sync_parent_dir(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => File::open(dir)?.sync_all(),
+        _ => File::open(".")?.sync_all(),
+    }
+}
+
+fn encode<K: Serialize, V: Serialize>(
+    record: &RecordRef<'_, K, V>,
+    buf: &mut Vec<u8>,
+) -> io::Result<()> {
+    let payload = serde_json::to_vec(record)?;
+    let len = u32::try_from(payload.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "record is too large"))?;
+    buf.extend_from_slice(&len.to_le_bytes());
+    buf.extend_from_slice(&crc32fast::hash(&payload).to_le_bytes());
+    buf.extend_from_slice(&payload);
+    Ok(())
+}
+
+// Decodes the first record in `buf`, returning it along with its on-disk
+// length, or `None` if the record is incomplete or corrupted.
+fn decode<K: DeserializeOwned, V: DeserializeOwned>(buf: &[u8]) -> Option<(Record<K, V>, usize)> {
+    let header = buf.get(..HEADER_LEN)?;
+    let len = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+    let checksum = u32::from_le_bytes(header[4..].try_into().unwrap());
+    let payload = buf.get(HEADER_LEN..HEADER_LEN.checked_add(len)?)?;
+    if crc32fast::hash(payload) != checksum {
+        return None;
+    }
+    let record = serde_json::from_slice(payload).ok()?;
+    Some((record, HEADER_LEN + len))
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use std::mem;
+
+    use super::*;
+    use crate::{User, UserRepositoryDyn};
+
+    fn user(id: u64) -> User {
+        User {
+            id,
+            email: format!("user{id}@example.com").into(),
+            activated: true,
+        }
+    }
+
+    #[test]
+    fn test_persists_across_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("users.log");
+
+        {
+            let mut storage = FileStorage::open(&path).unwrap();
+            storage.set(1, user(1));
+            storage.set(2, user(2));
+            storage.remove(&1);
+        }
+
+        let storage = FileStorage::<u64, User>::open(&path).unwrap();
+        assert_eq!(storage.get(&1).as_deref(), None);
+        assert_eq!(storage.get(&2).as_deref(), Some(&user(2)));
+    }
+
+    #[test]
+    fn test_recovers_from_torn_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("users.log");
+
+        {
+            let mut storage = FileStorage::open(&path).unwrap();
+            storage.set(1, user(1));
+            storage.set(2, user(2));
+        }
+        // Simulate a crash in the middle of writing the last record.
+        let len = fs::metadata(&path).unwrap().len();
+        OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(len - 3)
+            .unwrap();
+
+        {
+            let mut storage = FileStorage::<u64, User>::open(&path).unwrap();
+            assert_eq!(storage.get(&1).as_deref(), Some(&user(1)));
+            assert_eq!(storage.get(&2).as_deref(), None);
+            storage.set(3, user(3));
+        }
+
+        let storage = FileStorage::<u64, User>::open(&path).unwrap();
+        assert_eq!(storage.get(&1).as_deref(), Some(&user(1)));
+        assert_eq!(storage.get(&3).as_deref(), Some(&user(3)));
+    }
+
+    #[test]
+    fn test_discards_corrupted_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("users.log");
+
+        {
+            let mut storage = FileStorage::open(&path).unwrap();
+            storage.set(1, user(1));
+            storage.set(2, user(2));
+        }
+        let mut bytes = fs::read(&path).unwrap();
+        let last = bytes.len() - 2;
+        bytes[last] ^= 0xff;
+        fs::write(&path, bytes).unwrap();
+
+        let storage = FileStorage::<u64, User>::open(&path).unwrap();
+        assert_eq!(storage.get(&1).as_deref(), Some(&user(1)));
+        assert_eq!(storage.get(&2).as_deref(), None);
+    }
+
+    #[test]
+    fn test_write_after_failed_one_survives_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("users.log");
+        let mut storage = FileStorage::open(&path).unwrap();
+        storage.try_set(1, user(1)).unwrap();
+
+        // Fails the write, and the truncation following it.
+        let writable = mem::replace(&mut storage.file, File::open(&path).unwrap());
+        assert!(storage.try_set(2, user(2)).is_err());
+        // Simulates the partial record the failed write could leave behind.
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"partial")
+            .unwrap();
+        storage.file = writable;
+
+        storage.try_set(3, user(3)).unwrap();
+        drop(storage);
+        let storage = FileStorage::<u64, User>::open(&path).unwrap();
+        assert_eq!(storage.get(&1).as_deref(), Some(&user(1)));
+        assert_eq!(storage.get(&2), None);
+        assert_eq!(storage.get(&3).as_deref(), Some(&user(3)));
+    }
+
+    #[test]
+    fn test_compaction() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("users.log");
+
+        {
+            let mut storage = FileStorage::open(&path).unwrap().with_compact_threshold(10);
+            for i in 0..20 {
+                storage.set(1, user(i));
+            }
+            storage.set(2, user(2));
+            assert!(storage.records < 10);
+        }
+
+        let storage = FileStorage::<u64, User>::open(&path).unwrap();
+        assert_eq!(storage.get(&1).as_deref(), Some(&user(19)));
+        assert_eq!(storage.get(&2).as_deref(), Some(&user(2)));
+        assert!(!path.with_extension("compact").exists());
+    }
+
+    #[test]
+    fn test_failed_compaction_is_retried() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("users.log");
+        // Blocks compaction by occupying the path of the new log.
+        fs::create_dir(path.with_extension("compact")).unwrap();
+
+        let mut storage = FileStorage::open(&path).unwrap().with_compact_threshold(10);
+        for i in 0..20 {
+            storage.try_set(1, user(i)).unwrap();
+        }
+        assert_eq!(storage.records, 20);
+
+        fs::remove_dir(path.with_extension("compact")).unwrap();
+        storage.try_set(2, user(2)).unwrap();
+        assert_eq!(storage.records, 2);
+        drop(storage);
+
+        let storage = FileStorage::<u64, User>::open(&path).unwrap();
+        assert_eq!(storage.get(&1).as_deref(), Some(&user(19)));
+        assert_eq!(storage.get(&2).as_deref(), Some(&user(2)));
+    }
+
+    #[test]
+    fn test_dynamic_dispatch_with_file_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("users.log");
+
+        let mut repo = UserRepositoryDyn::new(Box::new(FileStorage::open(&path).unwrap()));
+        repo.add_user(user(1)).unwrap();
+        drop(repo);
+
+        let repo = UserRepositoryDyn::new(Box::new(FileStorage::open(&path).unwrap()));
+        assert_eq!(repo.get_user(1).as_deref(), Some(&user(1)));
+    }
+}
@@ -1,11 +1,45 @@
-use std::borrow::Cow;
+use std::borrow::{Borrow, Cow};
 use std::collections::HashMap;
+use std::{error, fmt};
+
+use serde::{Deserialize, Serialize};
+
+pub mod async_storage;
+pub mod cached_storage;
+pub mod file_storage;
+pub mod metrics_storage;
+pub mod read_only_storage;
+pub mod sqlite_storage;
+pub mod transaction;
+pub mod try_storage;
+
+pub use self::{
+    async_storage::{
+        AsyncAdapter, AsyncStorage, AsyncUserRepositoryDyn, AsyncUserRepositoryStatic,
+        StorageFuture,
+    },
+    cached_storage::CachedStorage,
+    file_storage::FileStorage,
+    metrics_storage::{Metrics, MetricsHandle, MetricsStorage},
+    read_only_storage::{ReadOnlyError, ReadOnlyStorage},
+    sqlite_storage::SqliteStorage,
+    transaction::Transaction,
+    try_storage::{
+        TryAdapter, TryRepositoryError, TryStorage, TryStorageIter, TryUserRepositoryDyn,
+        TryUserRepositoryStatic,
+    },
+};
 
 // Trait defining a generic Storage abstraction
-pub trait Storage<K, V> {
+//
+// `get` returns a `Cow`, so in-memory backends can lend their values while
+// backends not keeping them around (e.g. databases) hand out owned ones.
+pub trait Storage<K, V: Clone> {
     fn set(&mut self, key: K, val: V);
-    fn get(&self, key: &K) -> Option<&V>;
+    fn get(&self, key: &K) -> Option<Cow<'_, V>>;
     fn remove(&mut self, key: &K) -> Option<V>;
+    // Iterates over all the stored entries in no particular order.
+    fn iter(&self) -> Box<dyn Iterator<Item = (K, Cow<'_, V>)> + '_>;
 }
 
 // Implementation of Storage trait using HashMap
@@ -24,31 +58,207 @@ where
     }
 }
 
-impl<K, V> Storage<K, V> for HashMapStorage<K, V>
+impl<K, V> Default for HashMapStorage<K, V>
 where
     K: Eq + std::hash::Hash,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V> Storage<K, V> for HashMapStorage<K, V>
+where
+    K: Clone + Eq + std::hash::Hash,
+    V: Clone,
 {
     fn set(&mut self, key: K, val: V) {
         self.inner.insert(key, val);
     }
 
-    fn get(&self, key: &K) -> Option<&V> {
-        self.inner.get(key)
+    fn get(&self, key: &K) -> Option<Cow<'_, V>> {
+        self.inner.get(key).map(Cow::Borrowed)
     }
 
     fn remove(&mut self, key: &K) -> Option<V> {
         self.inner.remove(key)
     }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (K, Cow<'_, V>)> + '_> {
+        Box::new(
+            self.inner
+                .iter()
+                .map(|(k, v)| (k.clone(), Cow::Borrowed(v))),
+        )
+    }
 }
 
 // User entity
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct User {
     pub id: u64,
     pub email: Cow<'static, str>,
     pub activated: bool,
 }
 
+// Errors of UserRepository write operations
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RepositoryError {
+    // `add_user` was called with an `id` that is already stored.
+    AlreadyExists(u64),
+    // `update_user` was called with an `id` that is not stored.
+    NotFound(u64),
+    // The email is already used by another user.
+    EmailTaken(Cow<'static, str>),
+}
+
+impl fmt::Display for RepositoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AlreadyExists(id) => write!(f, "user {id} already exists"),
+            Self::NotFound(id) => write!(f, "user {id} not found"),
+            Self::EmailTaken(email) => write!(f, "email {email} is already taken"),
+        }
+    }
+}
+
+impl error::Error for RepositoryError {}
+
+// Repository rules shared by the blocking, fallible and async repositories,
+// over the users they load from their storages.
+//
+// The users are anything borrowing a `User`, so each storage flavour passes
+// them as it hands them out.
+mod rules {
+    use super::*;
+
+    // `add_user` requires the `id` not to be `stored` yet.
+    pub fn check_new(id: u64, stored: bool) -> Result<(), RepositoryError> {
+        if stored {
+            return Err(RepositoryError::AlreadyExists(id));
+        }
+        Ok(())
+    }
+
+    // `update_user` requires the `id` to be `stored` already.
+    pub fn check_existing(id: u64, stored: bool) -> Result<(), RepositoryError> {
+        if !stored {
+            return Err(RepositoryError::NotFound(id));
+        }
+        Ok(())
+    }
+
+    // Emails are unique regardless of their ASCII case.
+    pub fn check_email<U: Borrow<User>>(
+        users: impl IntoIterator<Item = U>,
+        user: &User,
+    ) -> Result<(), RepositoryError> {
+        match find_by_email(users, &user.email) {
+            Some(other) if other.borrow().id != user.id => {
+                Err(RepositoryError::EmailTaken(user.email.clone()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn list_users<U: Borrow<User>>(
+        users: impl IntoIterator<Item = U>,
+        offset: usize,
+        limit: usize,
+    ) -> Vec<U> {
+        let mut users: Vec<_> = users.into_iter().collect();
+        users.sort_unstable_by_key(|user| user.borrow().id);
+        users.into_iter().skip(offset).take(limit).collect()
+    }
+
+    pub fn find_by_email<U: Borrow<User>>(
+        users: impl IntoIterator<Item = U>,
+        email: &str,
+    ) -> Option<U> {
+        users
+            .into_iter()
+            .find(|user| user.borrow().email.eq_ignore_ascii_case(email))
+    }
+
+    pub fn list_activated<U: Borrow<User>>(users: impl IntoIterator<Item = U>) -> Vec<U> {
+        let mut users: Vec<_> = users
+            .into_iter()
+            .filter(|user| user.borrow().activated)
+            .collect();
+        users.sort_unstable_by_key(|user| user.borrow().id);
+        users
+    }
+}
+
+// Repository logic shared by both dispatch flavours, generic over `?Sized`
+// storage so `UserRepositoryDyn` can call it with `dyn Storage`.
+mod ops {
+    use super::*;
+
+    pub fn add_user<S>(storage: &mut S, user: User) -> Result<(), RepositoryError>
+    where
+        S: Storage<u64, User> + ?Sized,
+    {
+        rules::check_new(user.id, storage.get(&user.id).is_some())?;
+        rules::check_email(users(storage), &user)?;
+        storage.set(user.id, user);
+        Ok(())
+    }
+
+    pub fn update_user<S>(storage: &mut S, user: User) -> Result<(), RepositoryError>
+    where
+        S: Storage<u64, User> + ?Sized,
+    {
+        rules::check_existing(user.id, storage.get(&user.id).is_some())?;
+        rules::check_email(users(storage), &user)?;
+        storage.set(user.id, user);
+        Ok(())
+    }
+
+    pub fn list_users<S>(storage: &S, offset: usize, limit: usize) -> Vec<Cow<'_, User>>
+    where
+        S: Storage<u64, User> + ?Sized,
+    {
+        rules::list_users(users(storage), offset, limit)
+    }
+
+    pub fn find_by_email<'s, S>(storage: &'s S, email: &str) -> Option<Cow<'s, User>>
+    where
+        S: Storage<u64, User> + ?Sized,
+    {
+        rules::find_by_email(users(storage), email)
+    }
+
+    pub fn list_activated<S>(storage: &S) -> Vec<Cow<'_, User>>
+    where
+        S: Storage<u64, User> + ?Sized,
+    {
+        rules::list_activated(users(storage))
+    }
+
+    pub fn transaction<S, T, E>(
+        storage: &mut S,
+        f: impl FnOnce(&mut UserRepositoryStatic<Transaction<'_, u64, User, S>>) -> Result<T, E>,
+    ) -> Result<T, E>
+    where
+        S: Storage<u64, User> + ?Sized,
+    {
+        let mut repo = UserRepositoryStatic::new(Transaction::new(storage));
+        let res = f(&mut repo);
+        if res.is_ok() {
+            repo.storage.commit();
+        }
+        res
+    }
+
+    fn users<S>(storage: &S) -> impl Iterator<Item = Cow<'_, User>>
+    where
+        S: Storage<u64, User> + ?Sized,
+    {
+        storage.iter().map(|(_, user)| user)
+    }
+}
+
 // Dynamic Dispatch: UserRepository with trait objects
 pub struct UserRepositoryDyn {
     storage: Box<dyn Storage<u64, User>>,
@@ -59,21 +269,45 @@ impl UserRepositoryDyn {
         Self { storage }
     }
 
-    pub fn add_user(&mut self, user: User) {
-        self.storage.set(user.id, user);
+    pub fn add_user(&mut self, user: User) -> Result<(), RepositoryError> {
+        ops::add_user(&mut *self.storage, user)
     }
 
-    pub fn get_user(&self, id: u64) -> Option<&User> {
+    pub fn get_user(&self, id: u64) -> Option<Cow<'_, User>> {
         self.storage.get(&id)
     }
 
-    pub fn update_user(&mut self, user: User) {
-        self.storage.set(user.id, user);
+    pub fn update_user(&mut self, user: User) -> Result<(), RepositoryError> {
+        ops::update_user(&mut *self.storage, user)
     }
 
     pub fn remove_user(&mut self, id: u64) -> Option<User> {
         self.storage.remove(&id)
     }
+
+    // Returns at most `limit` users ordered by `id`, skipping the first `offset`.
+    pub fn list_users(&self, offset: usize, limit: usize) -> Vec<Cow<'_, User>> {
+        ops::list_users(&*self.storage, offset, limit)
+    }
+
+    pub fn find_by_email(&self, email: &str) -> Option<Cow<'_, User>> {
+        ops::find_by_email(&*self.storage, email)
+    }
+
+    pub fn list_activated(&self) -> Vec<Cow<'_, User>> {
+        ops::list_activated(&*self.storage)
+    }
+
+    // Runs `f` against a repository whose writes are committed only if it
+    // returns `Ok`, and discarded otherwise.
+    pub fn transaction<T, E>(
+        &mut self,
+        f: impl FnOnce(
+            &mut UserRepositoryStatic<Transaction<'_, u64, User, dyn Storage<u64, User>>>,
+        ) -> Result<T, E>,
+    ) -> Result<T, E> {
+        ops::transaction(&mut *self.storage, f)
+    }
 }
 
 // Static Dispatch: UserRepository with generic storage
@@ -92,19 +326,41 @@ where
         Self { storage }
     }
 
-    pub fn add_user(&mut self, user: User) {
-        self.storage.set(user.id, user);
+    pub fn add_user(&mut self, user: User) -> Result<(), RepositoryError> {
+        ops::add_user(&mut self.storage, user)
     }
 
-    pub fn get_user(&self, id: u64) -> Option<&User> {
+    pub fn get_user(&self, id: u64) -> Option<Cow<'_, User>> {
         self.storage.get(&id)
     }
 
-    pub fn update_user(&mut self, user: User) {
-        self.storage.set(user.id, user);
+    pub fn update_user(&mut self, user: User) -> Result<(), RepositoryError> {
+        ops::update_user(&mut self.storage, user)
     }
 
     pub fn remove_user(&mut self, id: u64) -> Option<User> {
         self.storage.remove(&id)
     }
+
+    // Returns at most `limit` users ordered by `id`, skipping the first `offset`.
+    pub fn list_users(&self, offset: usize, limit: usize) -> Vec<Cow<'_, User>> {
+        ops::list_users(&self.storage, offset, limit)
+    }
+
+    pub fn find_by_email(&self, email: &str) -> Option<Cow<'_, User>> {
+        ops::find_by_email(&self.storage, email)
+    }
+
+    pub fn list_activated(&self) -> Vec<Cow<'_, User>> {
+        ops::list_activated(&self.storage)
+    }
+
+    // Runs `f` against a repository whose writes are committed only if it
+    // returns `Ok`, and discarded otherwise.
+    pub fn transaction<T, E>(
+        &mut self,
+        f: impl FnOnce(&mut UserRepositoryStatic<Transaction<'_, u64, User, S>>) -> Result<T, E>,
+    ) -> Result<T, E> {
+        ops::transaction(&mut self.storage, f)
+    }
 }
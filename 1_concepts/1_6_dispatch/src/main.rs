@@ -11,7 +11,7 @@ fn main() {
         activated: true,
     };
 
-    dyn_repo.add_user(user1.clone());
+    dyn_repo.add_user(user1.clone()).unwrap();
     println!("Dynamic Dispatch - User added: {:?}", dyn_repo.get_user(1));
 
     // Static Dispatch Example
@@ -24,7 +24,7 @@ fn main() {
         activated: true,
     };
 
-    static_repo.add_user(user2.clone());
+    static_repo.add_user(user2.clone()).unwrap();
     println!(
         "Static Dispatch - User added: {:?}",
         static_repo.get_user(2)
@@ -34,7 +34,10 @@ fn main() {
 // Tests
 #[cfg(test)]
 mod tests {
+    use std::borrow::Cow;
+
     use super::*;
+    use task_1_6::RepositoryError;
 
     #[test]
     fn test_dynamic_dispatch() {
@@ -47,11 +50,11 @@ mod tests {
             activated: true,
         };
 
-        repo.add_user(user.clone());
-        assert_eq!(repo.get_user(1), Some(&user));
+        repo.add_user(user.clone()).unwrap();
+        assert_eq!(repo.get_user(1).as_deref(), Some(&user));
 
         repo.remove_user(1);
-        assert_eq!(repo.get_user(1), None);
+        assert_eq!(repo.get_user(1).as_deref(), None);
     }
 
     #[test]
@@ -65,10 +68,88 @@ mod tests {
             activated: true,
         };
 
-        repo.add_user(user.clone());
-        assert_eq!(repo.get_user(2), Some(&user));
+        repo.add_user(user.clone()).unwrap();
+        assert_eq!(repo.get_user(2).as_deref(), Some(&user));
 
         repo.remove_user(2);
+        assert_eq!(repo.get_user(2).as_deref(), None);
+    }
+
+    fn user(id: u64) -> User {
+        User {
+            id,
+            email: format!("user{id}@example.com").into(),
+            activated: id % 2 == 1,
+        }
+    }
+
+    #[test]
+    fn test_add_user_rejects_duplicates() {
+        let mut repo = UserRepositoryDyn::new(Box::new(HashMapStorage::new()));
+        repo.add_user(user(1)).unwrap();
+
+        let same_id = User {
+            email: "other@example.com".into(),
+            ..user(1)
+        };
+        assert_eq!(
+            repo.add_user(same_id),
+            Err(RepositoryError::AlreadyExists(1))
+        );
+
+        let same_email = User {
+            email: "USER1@example.com".into(),
+            ..user(2)
+        };
+        assert_eq!(
+            repo.add_user(same_email),
+            Err(RepositoryError::EmailTaken("USER1@example.com".into()))
+        );
+        assert_eq!(repo.get_user(1).as_deref(), Some(&user(1)));
         assert_eq!(repo.get_user(2), None);
     }
+
+    #[test]
+    fn test_update_user() {
+        let mut repo = UserRepositoryStatic::new(HashMapStorage::new());
+        repo.add_user(user(1)).unwrap();
+        repo.add_user(user(2)).unwrap();
+
+        assert_eq!(repo.update_user(user(3)), Err(RepositoryError::NotFound(3)));
+        assert_eq!(
+            repo.update_user(User {
+                email: user(2).email,
+                ..user(1)
+            }),
+            Err(RepositoryError::EmailTaken(user(2).email))
+        );
+
+        let updated = User {
+            activated: false,
+            ..user(1)
+        };
+        repo.update_user(updated.clone()).unwrap();
+        assert_eq!(repo.get_user(1).as_deref(), Some(&updated));
+    }
+
+    #[test]
+    fn test_queries() {
+        let mut repo = UserRepositoryStatic::new(HashMapStorage::new());
+        for id in (1..=5).rev() {
+            repo.add_user(user(id)).unwrap();
+        }
+
+        let ids = |users: Vec<Cow<'_, User>>| users.iter().map(|u| u.id).collect::<Vec<u64>>();
+        assert_eq!(ids(repo.list_users(0, 2)), [1, 2]);
+        assert_eq!(ids(repo.list_users(2, 2)), [3, 4]);
+        assert_eq!(ids(repo.list_users(4, 2)), [5]);
+        assert!(repo.list_users(6, 2).is_empty());
+        assert_eq!(ids(repo.list_activated()), [1, 3, 5]);
+
+        assert_eq!(
+            repo.find_by_email("user4@example.com").map(|u| u.id),
+            Some(4)
+        );
+        assert_eq!(repo.find_by_email("nobody@example.com"), None);
+    }
 }
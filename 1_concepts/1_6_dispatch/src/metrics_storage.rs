@@ -0,0 +1,156 @@
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::Storage;
+
+// Counters collected by MetricsStorage
+//
+// Latencies are the total time spent in the wrapped storage by each kind of
+// operation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Metrics {
+    pub hits: u64,
+    pub misses: u64,
+    pub sets: u64,
+    pub removes: u64,
+    pub get_latency: Duration,
+    pub set_latency: Duration,
+    pub remove_latency: Duration,
+}
+
+// Shared handle to the Metrics collected by a MetricsStorage
+//
+// It's `Send` and `Sync`, so the metrics can be read from another thread than
+// the one using the storage.
+#[derive(Clone, Debug, Default)]
+pub struct MetricsHandle(Arc<Mutex<Metrics>>);
+
+impl MetricsHandle {
+    // Returns a snapshot of the metrics collected so far.
+    pub fn get(&self) -> Metrics {
+        *self.0.lock().unwrap()
+    }
+}
+
+// Storage decorator recording Metrics of every operation on the wrapped storage
+pub struct MetricsStorage<S> {
+    inner: S,
+    metrics: MetricsHandle,
+}
+
+impl<S> MetricsStorage<S> {
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            metrics: MetricsHandle::default(),
+        }
+    }
+
+    // Returns a handle to the collected metrics, which stays readable after
+    // this storage is moved into a repository.
+    pub fn metrics(&self) -> MetricsHandle {
+        self.metrics.clone()
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    fn record(&self, f: impl FnOnce(&mut Metrics)) {
+        f(&mut self.metrics.0.lock().unwrap());
+    }
+}
+
+impl<K, V, S> Storage<K, V> for MetricsStorage<S>
+where
+    V: Clone,
+    S: Storage<K, V>,
+{
+    fn set(&mut self, key: K, val: V) {
+        let start = Instant::now();
+        self.inner.set(key, val);
+        let elapsed = start.elapsed();
+        self.record(|m| {
+            m.sets += 1;
+            m.set_latency += elapsed;
+        });
+    }
+
+    fn get(&self, key: &K) -> Option<Cow<'_, V>> {
+        let start = Instant::now();
+        let val = self.inner.get(key);
+        let elapsed = start.elapsed();
+        self.record(|m| {
+            if val.is_some() {
+                m.hits += 1;
+            } else {
+                m.misses += 1;
+            }
+            m.get_latency += elapsed;
+        });
+        val
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        let start = Instant::now();
+        let val = self.inner.remove(key);
+        let elapsed = start.elapsed();
+        self.record(|m| {
+            m.removes += 1;
+            m.remove_latency += elapsed;
+        });
+        val
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (K, Cow<'_, V>)> + '_> {
+        self.inner.iter()
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::{HashMapStorage, User, UserRepositoryDyn, UserRepositoryStatic};
+
+    #[test]
+    fn test_counts_operations() {
+        let storage = MetricsStorage::new(HashMapStorage::new());
+        let metrics = storage.metrics();
+        let mut repo = UserRepositoryDyn::new(Box::new(storage));
+
+        let user = User {
+            id: 1,
+            email: "user1@example.com".into(),
+            activated: true,
+        };
+        repo.add_user(user).unwrap();
+        repo.get_user(1);
+        repo.get_user(2);
+        repo.remove_user(1);
+
+        let metrics = metrics.get();
+        // `add_user` checks whether the user exists first.
+        assert_eq!(metrics.hits, 1);
+        assert_eq!(metrics.misses, 2);
+        assert_eq!(metrics.sets, 1);
+        assert_eq!(metrics.removes, 1);
+    }
+
+    #[test]
+    fn test_is_send() {
+        let storage = MetricsStorage::new(HashMapStorage::new());
+        let metrics = storage.metrics();
+        let repo = UserRepositoryStatic::new(storage);
+
+        thread::spawn(move || {
+            repo.get_user(1);
+        })
+        .join()
+        .unwrap();
+        assert_eq!(metrics.get().misses, 1);
+    }
+}
@@ -0,0 +1,142 @@
+use std::borrow::Cow;
+use std::{error, fmt};
+
+use crate::{Storage, TryStorage, TryStorageIter};
+
+// Error returned by ReadOnlyStorage on any write attempt
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReadOnlyError;
+
+impl fmt::Display for ReadOnlyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("storage is read-only")
+    }
+}
+
+impl error::Error for ReadOnlyError {}
+
+// Storage decorator rejecting all writes to the wrapped storage
+//
+// It doesn't implement `Storage`, as its writes can't fail. Reads are
+// available right on it, and through its `TryStorage` implementation, whose
+// writes return a `ReadOnlyError`. So repositories are built over it with
+// `TryUserRepositoryDyn` and `TryUserRepositoryStatic`, the fallible
+// flavours of `UserRepositoryDyn` and `UserRepositoryStatic`.
+pub struct ReadOnlyStorage<S> {
+    inner: S,
+}
+
+impl<S> ReadOnlyStorage<S> {
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    pub fn get<K, V>(&self, key: &K) -> Option<Cow<'_, V>>
+    where
+        V: Clone,
+        S: Storage<K, V>,
+    {
+        self.inner.get(key)
+    }
+
+    // Iterates over all the stored entries in no particular order.
+    pub fn iter<K, V>(&self) -> Box<dyn Iterator<Item = (K, Cow<'_, V>)> + '_>
+    where
+        V: Clone,
+        S: Storage<K, V>,
+    {
+        self.inner.iter()
+    }
+}
+
+impl<K, V, S> TryStorage<K, V> for ReadOnlyStorage<S>
+where
+    V: Clone,
+    S: Storage<K, V>,
+{
+    type Error = ReadOnlyError;
+
+    fn try_set(&mut self, _: K, _: V) -> Result<(), Self::Error> {
+        Err(ReadOnlyError)
+    }
+
+    fn try_get(&self, key: &K) -> Result<Option<Cow<'_, V>>, Self::Error> {
+        Ok(self.inner.get(key))
+    }
+
+    fn try_remove(&mut self, _: &K) -> Result<Option<V>, Self::Error> {
+        Err(ReadOnlyError)
+    }
+
+    fn try_iter(&self) -> Result<TryStorageIter<'_, K, V>, Self::Error> {
+        Ok(self.inner.iter())
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        CachedStorage, HashMapStorage, MetricsStorage, TryRepositoryError, TryUserRepositoryDyn,
+        TryUserRepositoryStatic, User,
+    };
+
+    fn user(id: u64) -> User {
+        User {
+            id,
+            email: format!("user{id}@example.com").into(),
+            activated: true,
+        }
+    }
+
+    fn populated() -> HashMapStorage<u64, User> {
+        let mut storage = HashMapStorage::new();
+        storage.set(1, user(1));
+        storage
+    }
+
+    #[test]
+    fn test_reads_pass_through() {
+        let storage = ReadOnlyStorage::new(CachedStorage::new(populated(), 8));
+
+        assert_eq!(storage.get(&1).as_deref(), Some(&user(1)));
+        assert_eq!(storage.get(&2), None);
+        assert_eq!(storage.iter().count(), 1);
+    }
+
+    #[test]
+    fn test_rejects_writes() {
+        let mut repo = TryUserRepositoryStatic::new(ReadOnlyStorage::new(populated()));
+
+        assert_eq!(
+            repo.add_user(user(2)),
+            Err(TryRepositoryError::Storage(ReadOnlyError))
+        );
+        assert_eq!(repo.remove_user(1), Err(ReadOnlyError));
+        assert_eq!(repo.get_user(1).unwrap().as_deref(), Some(&user(1)));
+    }
+
+    #[test]
+    fn test_layered_dynamic_repository() {
+        let storage = ReadOnlyStorage::new(MetricsStorage::new(CachedStorage::new(populated(), 8)));
+        let mut repo: TryUserRepositoryDyn<ReadOnlyError> =
+            TryUserRepositoryDyn::new(Box::new(storage));
+
+        assert_eq!(repo.get_user(1).unwrap().as_deref(), Some(&user(1)));
+        assert_eq!(
+            repo.find_by_email("USER1@example.com").unwrap().as_deref(),
+            Some(&user(1))
+        );
+        assert_eq!(repo.list_users(0, 10).unwrap().len(), 1);
+        assert_eq!(repo.list_activated().unwrap().len(), 1);
+        assert_eq!(
+            repo.update_user(user(1)),
+            Err(TryRepositoryError::Storage(ReadOnlyError))
+        );
+    }
+}
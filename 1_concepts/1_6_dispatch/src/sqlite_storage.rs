@@ -0,0 +1,220 @@
+use std::borrow::Cow;
+use std::path::Path;
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+use crate::{Storage, TryStorage, TryStorageIter, User};
+
+// Schema migrations, applied in order. The index of the last applied one is
+// tracked in SQLite's `user_version` pragma.
+const MIGRATIONS: &[&str] = &["CREATE TABLE users (
+        id INTEGER PRIMARY KEY NOT NULL,
+        email TEXT NOT NULL,
+        activated INTEGER NOT NULL
+    )"];
+
+// Implementation of Storage trait over an embedded SQLite database.
+//
+// The storage key is used as the `id` column, so it's expected to be equal to
+// `User::id`, as it is in the `UserRepository*` types.
+pub struct SqliteStorage {
+    conn: Connection,
+}
+
+impl SqliteStorage {
+    // Opens (or creates) the database file at `path` and migrates it.
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    // Creates a fresh database living only in memory.
+    pub fn open_in_memory() -> rusqlite::Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(mut conn: Connection) -> rusqlite::Result<Self> {
+        migrate(&mut conn)?;
+        Ok(Self { conn })
+    }
+}
+
+fn migrate(conn: &mut Connection) -> rusqlite::Result<()> {
+    let version: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", i + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+// Builds a `User` from an `(email, activated)` row.
+fn read_user(id: u64, row: &Row<'_>) -> rusqlite::Result<User> {
+    Ok(User {
+        id,
+        email: row.get::<_, String>(0)?.into(),
+        activated: row.get(1)?,
+    })
+}
+
+impl TryStorage<u64, User> for SqliteStorage {
+    type Error = rusqlite::Error;
+
+    fn try_set(&mut self, key: u64, val: User) -> rusqlite::Result<()> {
+        upsert(&self.conn, key, &val)
+    }
+
+    fn try_get(&self, key: &u64) -> rusqlite::Result<Option<Cow<'_, User>>> {
+        let user = self
+            .conn
+            .query_row(
+                "SELECT email, activated FROM users WHERE id = ?1",
+                [key],
+                |row| read_user(*key, row),
+            )
+            .optional()?;
+        Ok(user.map(Cow::Owned))
+    }
+
+    fn try_remove(&mut self, key: &u64) -> rusqlite::Result<Option<User>> {
+        delete(&self.conn, *key)
+    }
+
+    fn try_iter(&self) -> rusqlite::Result<TryStorageIter<'_, u64, User>> {
+        let users = self
+            .conn
+            .prepare("SELECT email, activated, id FROM users")?
+            .query_map([], |row| read_user(row.get(2)?, row))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        Ok(Box::new(
+            users.into_iter().map(|user| (user.id, Cow::Owned(user))),
+        ))
+    }
+
+    // Applies the `writes` in a single SQLite transaction, which is rolled
+    // back on failure.
+    fn apply_batch(&mut self, writes: Vec<(u64, Option<User>)>) -> rusqlite::Result<()> {
+        let tx = self.conn.transaction()?;
+        for (key, val) in writes {
+            match val {
+                Some(val) => upsert(&tx, key, &val)?,
+                None => {
+                    delete(&tx, key)?;
+                }
+            }
+        }
+        tx.commit()
+    }
+}
+
+fn upsert(conn: &Connection, key: u64, val: &User) -> rusqlite::Result<()> {
+    conn.execute(
+        "INSERT INTO users (id, email, activated) VALUES (?1, ?2, ?3)
+         ON CONFLICT (id) DO UPDATE
+         SET email = excluded.email, activated = excluded.activated",
+        params![key, val.email, val.activated],
+    )?;
+    Ok(())
+}
+
+fn delete(conn: &Connection, key: u64) -> rusqlite::Result<Option<User>> {
+    conn.query_row(
+        "DELETE FROM users WHERE id = ?1 RETURNING email, activated",
+        [key],
+        |row| read_user(key, row),
+    )
+    .optional()
+}
+
+// Panics on database errors, use `TryStorage` to handle them instead.
+impl Storage<u64, User> for SqliteStorage {
+    fn set(&mut self, key: u64, val: User) {
+        self.try_set(key, val).expect("failed to store user");
+    }
+
+    fn get(&self, key: &u64) -> Option<Cow<'_, User>> {
+        self.try_get(key).expect("failed to load user")
+    }
+
+    fn remove(&mut self, key: &u64) -> Option<User> {
+        self.try_remove(key).expect("failed to remove user")
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (u64, Cow<'_, User>)> + '_> {
+        self.try_iter().expect("failed to load users")
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{UserRepositoryDyn, UserRepositoryStatic};
+
+    fn user(id: u64) -> User {
+        User {
+            id,
+            email: format!("user{id}@example.com").into(),
+            activated: id.is_multiple_of(2),
+        }
+    }
+
+    #[test]
+    fn test_set_get_remove() {
+        let mut storage = SqliteStorage::open_in_memory().unwrap();
+
+        storage.set(1, user(1));
+        storage.set(2, user(2));
+        assert_eq!(storage.get(&1).as_deref(), Some(&user(1)));
+
+        let mut updated = user(1);
+        updated.activated = true;
+        storage.set(1, updated.clone());
+        assert_eq!(storage.get(&1).as_deref(), Some(&updated));
+
+        assert_eq!(storage.remove(&1), Some(updated));
+        assert_eq!(storage.remove(&1), None);
+        assert_eq!(storage.get(&1), None);
+        assert_eq!(storage.get(&2).as_deref(), Some(&user(2)));
+    }
+
+    #[test]
+    fn test_persists_across_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("users.db");
+
+        SqliteStorage::open(&path).unwrap().set(1, user(1));
+
+        let storage = SqliteStorage::open(&path).unwrap();
+        assert_eq!(storage.get(&1).as_deref(), Some(&user(1)));
+    }
+
+    #[test]
+    fn test_dynamic_dispatch_with_sqlite_storage() {
+        let storage = Box::new(SqliteStorage::open_in_memory().unwrap());
+        let mut repo = UserRepositoryDyn::new(storage);
+
+        repo.add_user(user(1)).unwrap();
+        assert_eq!(repo.get_user(1).as_deref(), Some(&user(1)));
+
+        repo.remove_user(1);
+        assert_eq!(repo.get_user(1), None);
+    }
+
+    #[test]
+    fn test_static_dispatch_with_sqlite_storage() {
+        let storage = SqliteStorage::open_in_memory().unwrap();
+        let mut repo = UserRepositoryStatic::new(storage);
+
+        repo.add_user(user(2)).unwrap();
+        assert_eq!(repo.get_user(2).as_deref(), Some(&user(2)));
+        assert_eq!(
+            repo.find_by_email("user2@example.com").as_deref(),
+            Some(&user(2))
+        );
+
+        repo.remove_user(2);
+        assert_eq!(repo.get_user(2), None);
+    }
+}
@@ -0,0 +1,222 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::{Storage, TryStorage};
+
+// Unit of work buffering writes against the borrowed storage
+//
+// Reads see the buffered writes, while the storage itself is left untouched
+// until `commit`. Dropping the transaction without committing it discards
+// all the writes.
+//
+// `commit` applies the writes one by one, so it's atomic for in-memory
+// storages only, while `try_commit` applies them all or none through
+// `TryStorage::apply_batch`.
+pub struct Transaction<'s, K, V, S: ?Sized> {
+    storage: &'s mut S,
+    // `None` marks a removed key.
+    writes: HashMap<K, Option<V>>,
+}
+
+impl<'s, K, V, S> Transaction<'s, K, V, S>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+    S: Storage<K, V> + ?Sized,
+{
+    pub fn new(storage: &'s mut S) -> Self {
+        Self {
+            storage,
+            writes: HashMap::new(),
+        }
+    }
+
+    // Applies all the buffered writes to the storage.
+    //
+    // Storages failing to write panic, use `try_commit` to handle that instead.
+    pub fn commit(self) {
+        for (key, val) in self.writes {
+            match val {
+                Some(val) => self.storage.set(key, val),
+                None => {
+                    self.storage.remove(&key);
+                }
+            }
+        }
+    }
+
+    // Applies all the buffered writes to the storage, or none of them if one
+    // fails.
+    pub fn try_commit(self) -> Result<(), S::Error>
+    where
+        S: TryStorage<K, V>,
+    {
+        self.storage.apply_batch(self.writes.into_iter().collect())
+    }
+
+    // Discards all the buffered writes.
+    pub fn rollback(self) {}
+}
+
+impl<K, V, S> Storage<K, V> for Transaction<'_, K, V, S>
+where
+    K: Clone + Eq + Hash,
+    V: Clone,
+    S: Storage<K, V> + ?Sized,
+{
+    fn set(&mut self, key: K, val: V) {
+        self.writes.insert(key, Some(val));
+    }
+
+    fn get(&self, key: &K) -> Option<Cow<'_, V>> {
+        match self.writes.get(key) {
+            Some(val) => val.as_ref().map(Cow::Borrowed),
+            None => self.storage.get(key),
+        }
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        let val = self.get(key).map(Cow::into_owned);
+        if val.is_some() {
+            self.writes.insert(key.clone(), None);
+        }
+        val
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (K, Cow<'_, V>)> + '_> {
+        let stored = self
+            .storage
+            .iter()
+            .filter(|(key, _)| !self.writes.contains_key(key));
+        let written = self
+            .writes
+            .iter()
+            .filter_map(|(key, val)| Some((key.clone(), Cow::Borrowed(val.as_ref()?))));
+        Box::new(stored.chain(written))
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        HashMapStorage, RepositoryError, SqliteStorage, User, UserRepositoryDyn,
+        UserRepositoryStatic,
+    };
+
+    fn user(id: u64) -> User {
+        User {
+            id,
+            email: format!("user{id}@example.com").into(),
+            activated: false,
+        }
+    }
+
+    fn activated(id: u64) -> User {
+        User {
+            activated: true,
+            ..user(id)
+        }
+    }
+
+    #[test]
+    fn test_reads_own_writes() {
+        let mut storage = HashMapStorage::new();
+        storage.set(1, user(1));
+        storage.set(2, user(2));
+
+        let mut tx = Transaction::new(&mut storage);
+        tx.set(1, activated(1));
+        tx.set(3, user(3));
+        assert_eq!(tx.remove(&2), Some(user(2)));
+        assert_eq!(tx.remove(&2), None);
+
+        assert_eq!(tx.get(&1).as_deref(), Some(&activated(1)));
+        assert_eq!(tx.get(&2), None);
+        assert_eq!(tx.get(&3).as_deref(), Some(&user(3)));
+
+        let mut entries: Vec<_> = tx.iter().map(|(k, v)| (k, v.into_owned())).collect();
+        entries.sort_unstable_by_key(|(k, _)| *k);
+        assert_eq!(entries, [(1, activated(1)), (3, user(3))]);
+    }
+
+    #[test]
+    fn test_commit_and_rollback() {
+        let mut storage = HashMapStorage::new();
+        storage.set(1, user(1));
+
+        let mut tx = Transaction::new(&mut storage);
+        tx.set(1, activated(1));
+        tx.rollback();
+        assert_eq!(storage.get(&1).as_deref(), Some(&user(1)));
+
+        let mut tx = Transaction::new(&mut storage);
+        tx.set(1, activated(1));
+        tx.set(2, user(2));
+        tx.commit();
+        assert_eq!(storage.get(&1).as_deref(), Some(&activated(1)));
+        assert_eq!(storage.get(&2).as_deref(), Some(&user(2)));
+    }
+
+    #[test]
+    fn test_try_commit_surfaces_errors() {
+        let mut storage = SqliteStorage::open_in_memory().unwrap();
+        storage.set(1, user(1));
+
+        let mut tx = Transaction::new(&mut storage);
+        tx.remove(&1);
+        tx.set(2, user(2));
+        assert_eq!(tx.try_commit(), Ok(()));
+        assert_eq!(storage.get(&1), None);
+        assert_eq!(storage.get(&2).as_deref(), Some(&user(2)));
+
+        // `u64` ids beyond `i64::MAX` can't be stored in SQLite.
+        let mut tx = Transaction::new(&mut storage);
+        tx.set(3, user(3));
+        tx.set(2, activated(2));
+        tx.remove(&2);
+        tx.set(u64::MAX, user(u64::MAX));
+        assert!(tx.try_commit().is_err());
+        assert_eq!(storage.get(&2).as_deref(), Some(&user(2)));
+        assert_eq!(storage.get(&3), None);
+        assert_eq!(storage.iter().count(), 1);
+    }
+
+    #[test]
+    fn test_failed_transaction_leaves_storage_untouched() {
+        let mut repo = UserRepositoryDyn::new(Box::new(HashMapStorage::new()));
+        repo.add_user(user(1)).unwrap();
+
+        let res = repo.transaction(|tx| {
+            tx.update_user(activated(1))?;
+            tx.add_user(user(2))?;
+            tx.add_user(user(1))
+        });
+        assert_eq!(res, Err(RepositoryError::AlreadyExists(1)));
+
+        assert_eq!(repo.get_user(1).as_deref(), Some(&user(1)));
+        assert_eq!(repo.get_user(2), None);
+    }
+
+    #[test]
+    fn test_successful_transaction_is_committed() {
+        let mut repo = UserRepositoryStatic::new(HashMapStorage::new());
+        repo.add_user(user(1)).unwrap();
+        repo.add_user(user(2)).unwrap();
+
+        let res = repo.transaction(|tx| {
+            for id in [1, 2] {
+                tx.update_user(activated(id))?;
+            }
+            tx.remove_user(1);
+            assert_eq!(tx.list_activated().len(), 1);
+            Ok::<_, RepositoryError>(tx.list_users(0, 10).len())
+        });
+        assert_eq!(res, Ok(1));
+
+        assert_eq!(repo.get_user(1), None);
+        assert_eq!(repo.get_user(2).as_deref(), Some(&activated(2)));
+    }
+}
@@ -0,0 +1,426 @@
+use std::borrow::Cow;
+use std::convert::Infallible;
+use std::{error, fmt};
+
+use crate::{RepositoryError, Storage, User};
+
+// Boxed iterator returned by `TryStorage::try_iter`
+pub type TryStorageIter<'a, K, V> = Box<dyn Iterator<Item = (K, Cow<'a, V>)> + 'a>;
+
+// Fallible counterpart of the Storage trait, for backends doing I/O
+pub trait TryStorage<K, V: Clone> {
+    type Error;
+
+    fn try_set(&mut self, key: K, val: V) -> Result<(), Self::Error>;
+    fn try_get(&self, key: &K) -> Result<Option<Cow<'_, V>>, Self::Error>;
+    fn try_remove(&mut self, key: &K) -> Result<Option<V>, Self::Error>;
+    // Iterates over all the stored entries in no particular order.
+    fn try_iter(&self) -> Result<TryStorageIter<'_, K, V>, Self::Error>;
+
+    // Applies all the `writes` or none of them, a `None` value removing its
+    // key.
+    //
+    // By default, they're applied one by one, and the applied ones are undone
+    // if one fails. Undoing is best effort, as the storage has just failed, so
+    // storages supporting transactions should override it.
+    fn apply_batch(&mut self, writes: Vec<(K, Option<V>)>) -> Result<(), Self::Error>
+    where
+        K: Clone,
+    {
+        let mut applied = Vec::with_capacity(writes.len());
+        for (key, val) in writes {
+            let res = self
+                .try_get(&key)
+                .map(|prev| prev.map(Cow::into_owned))
+                .and_then(|prev| write(self, key.clone(), val).map(|()| prev));
+            match res {
+                Ok(prev) => applied.push((key, prev)),
+                Err(e) => {
+                    for (key, prev) in applied.into_iter().rev() {
+                        let _ = write(self, key, prev);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Sets the `key` to the `val`, or removes it if there's none.
+fn write<K, V, S>(storage: &mut S, key: K, val: Option<V>) -> Result<(), S::Error>
+where
+    V: Clone,
+    S: TryStorage<K, V> + ?Sized,
+{
+    match val {
+        Some(val) => storage.try_set(key, val),
+        None => storage.try_remove(&key).map(drop),
+    }
+}
+
+// Errors of fallible UserRepository write operations
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TryRepositoryError<E> {
+    // The storage failed.
+    Storage(E),
+    // The write breaks the same rules as in the infallible repositories.
+    Repository(RepositoryError),
+}
+
+impl<E: fmt::Display> fmt::Display for TryRepositoryError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Storage(e) => write!(f, "storage failed: {e}"),
+            Self::Repository(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl<E: error::Error + 'static> error::Error for TryRepositoryError<E> {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Storage(e) => Some(e),
+            Self::Repository(e) => Some(e),
+        }
+    }
+}
+
+impl<E> From<RepositoryError> for TryRepositoryError<E> {
+    fn from(e: RepositoryError) -> Self {
+        Self::Repository(e)
+    }
+}
+
+// Adapter making any infallible Storage usable as a TryStorage
+pub struct TryAdapter<S>(pub S);
+
+impl<K, V, S> TryStorage<K, V> for TryAdapter<S>
+where
+    V: Clone,
+    S: Storage<K, V>,
+{
+    type Error = Infallible;
+
+    fn try_set(&mut self, key: K, val: V) -> Result<(), Self::Error> {
+        self.0.set(key, val);
+        Ok(())
+    }
+
+    fn try_get(&self, key: &K) -> Result<Option<Cow<'_, V>>, Self::Error> {
+        Ok(self.0.get(key))
+    }
+
+    fn try_remove(&mut self, key: &K) -> Result<Option<V>, Self::Error> {
+        Ok(self.0.remove(key))
+    }
+
+    fn try_iter(&self) -> Result<TryStorageIter<'_, K, V>, Self::Error> {
+        Ok(self.0.iter())
+    }
+}
+
+// Fallible counterpart of the repository logic shared by both dispatch
+// flavours
+mod ops {
+    use super::*;
+    use crate::rules;
+
+    type OpResult<T, S> = Result<T, TryRepositoryError<<S as TryStorage<u64, User>>::Error>>;
+
+    pub fn add_user<S>(storage: &mut S, user: User) -> OpResult<(), S>
+    where
+        S: TryStorage<u64, User> + ?Sized,
+    {
+        rules::check_new(user.id, stored(storage, user.id)?)?;
+        rules::check_email(users(storage).map_err(TryRepositoryError::Storage)?, &user)?;
+        storage
+            .try_set(user.id, user)
+            .map_err(TryRepositoryError::Storage)
+    }
+
+    pub fn update_user<S>(storage: &mut S, user: User) -> OpResult<(), S>
+    where
+        S: TryStorage<u64, User> + ?Sized,
+    {
+        rules::check_existing(user.id, stored(storage, user.id)?)?;
+        rules::check_email(users(storage).map_err(TryRepositoryError::Storage)?, &user)?;
+        storage
+            .try_set(user.id, user)
+            .map_err(TryRepositoryError::Storage)
+    }
+
+    pub fn list_users<S>(
+        storage: &S,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Vec<Cow<'_, User>>, S::Error>
+    where
+        S: TryStorage<u64, User> + ?Sized,
+    {
+        Ok(rules::list_users(users(storage)?, offset, limit))
+    }
+
+    pub fn find_by_email<'s, S>(
+        storage: &'s S,
+        email: &str,
+    ) -> Result<Option<Cow<'s, User>>, S::Error>
+    where
+        S: TryStorage<u64, User> + ?Sized,
+    {
+        Ok(rules::find_by_email(users(storage)?, email))
+    }
+
+    pub fn list_activated<S>(storage: &S) -> Result<Vec<Cow<'_, User>>, S::Error>
+    where
+        S: TryStorage<u64, User> + ?Sized,
+    {
+        Ok(rules::list_activated(users(storage)?))
+    }
+
+    fn stored<S>(storage: &S, id: u64) -> OpResult<bool, S>
+    where
+        S: TryStorage<u64, User> + ?Sized,
+    {
+        let user = storage.try_get(&id).map_err(TryRepositoryError::Storage)?;
+        Ok(user.is_some())
+    }
+
+    fn users<S>(storage: &S) -> Result<impl Iterator<Item = Cow<'_, User>>, S::Error>
+    where
+        S: TryStorage<u64, User> + ?Sized,
+    {
+        Ok(storage.try_iter()?.map(|(_, user)| user))
+    }
+}
+
+// Dynamic Dispatch: fallible UserRepository with trait objects
+pub struct TryUserRepositoryDyn<E> {
+    storage: Box<dyn TryStorage<u64, User, Error = E>>,
+}
+
+impl<E> TryUserRepositoryDyn<E> {
+    pub fn new(storage: Box<dyn TryStorage<u64, User, Error = E>>) -> Self {
+        Self { storage }
+    }
+
+    pub fn add_user(&mut self, user: User) -> Result<(), TryRepositoryError<E>> {
+        ops::add_user(&mut *self.storage, user)
+    }
+
+    pub fn get_user(&self, id: u64) -> Result<Option<Cow<'_, User>>, E> {
+        self.storage.try_get(&id)
+    }
+
+    pub fn update_user(&mut self, user: User) -> Result<(), TryRepositoryError<E>> {
+        ops::update_user(&mut *self.storage, user)
+    }
+
+    pub fn remove_user(&mut self, id: u64) -> Result<Option<User>, E> {
+        self.storage.try_remove(&id)
+    }
+
+    // Returns at most `limit` users ordered by `id`, skipping the first `offset`.
+    pub fn list_users(&self, offset: usize, limit: usize) -> Result<Vec<Cow<'_, User>>, E> {
+        ops::list_users(&*self.storage, offset, limit)
+    }
+
+    pub fn find_by_email(&self, email: &str) -> Result<Option<Cow<'_, User>>, E> {
+        ops::find_by_email(&*self.storage, email)
+    }
+
+    pub fn list_activated(&self) -> Result<Vec<Cow<'_, User>>, E> {
+        ops::list_activated(&*self.storage)
+    }
+}
+
+// Static Dispatch: fallible UserRepository with generic storage
+pub struct TryUserRepositoryStatic<S>
+where
+    S: TryStorage<u64, User>,
+{
+    storage: S,
+}
+
+impl<S> TryUserRepositoryStatic<S>
+where
+    S: TryStorage<u64, User>,
+{
+    pub fn new(storage: S) -> Self {
+        Self { storage }
+    }
+
+    pub fn add_user(&mut self, user: User) -> Result<(), TryRepositoryError<S::Error>> {
+        ops::add_user(&mut self.storage, user)
+    }
+
+    pub fn get_user(&self, id: u64) -> Result<Option<Cow<'_, User>>, S::Error> {
+        self.storage.try_get(&id)
+    }
+
+    pub fn update_user(&mut self, user: User) -> Result<(), TryRepositoryError<S::Error>> {
+        ops::update_user(&mut self.storage, user)
+    }
+
+    pub fn remove_user(&mut self, id: u64) -> Result<Option<User>, S::Error> {
+        self.storage.try_remove(&id)
+    }
+
+    // Returns at most `limit` users ordered by `id`, skipping the first `offset`.
+    pub fn list_users(&self, offset: usize, limit: usize) -> Result<Vec<Cow<'_, User>>, S::Error> {
+        ops::list_users(&self.storage, offset, limit)
+    }
+
+    pub fn find_by_email(&self, email: &str) -> Result<Option<Cow<'_, User>>, S::Error> {
+        ops::find_by_email(&self.storage, email)
+    }
+
+    pub fn list_activated(&self) -> Result<Vec<Cow<'_, User>>, S::Error> {
+        ops::list_activated(&self.storage)
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+    use crate::{FileStorage, HashMapStorage, SqliteStorage};
+
+    fn user(id: u64) -> User {
+        User {
+            id,
+            email: format!("user{id}@example.com").into(),
+            activated: true,
+        }
+    }
+
+    #[test]
+    fn test_try_adapter_never_fails() {
+        let mut repo = TryUserRepositoryStatic::new(TryAdapter(HashMapStorage::new()));
+
+        repo.add_user(user(1)).unwrap();
+        assert_eq!(repo.get_user(1).unwrap().as_deref(), Some(&user(1)));
+        assert_eq!(repo.remove_user(1).unwrap(), Some(user(1)));
+        assert_eq!(repo.get_user(1).unwrap(), None);
+    }
+
+    #[test]
+    fn test_rejects_duplicate_ids() {
+        let mut repo = TryUserRepositoryStatic::new(SqliteStorage::open_in_memory().unwrap());
+        repo.add_user(user(1)).unwrap();
+
+        let mut duplicate = user(1);
+        duplicate.email = "other@example.com".into();
+        assert_eq!(
+            repo.add_user(duplicate),
+            Err(RepositoryError::AlreadyExists(1).into())
+        );
+        assert_eq!(repo.get_user(1).unwrap().as_deref(), Some(&user(1)));
+        assert_eq!(
+            repo.update_user(user(2)),
+            Err(RepositoryError::NotFound(2).into())
+        );
+    }
+
+    #[test]
+    fn test_rejects_taken_emails() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = FileStorage::open(dir.path().join("users.log")).unwrap();
+        let mut repo: TryUserRepositoryDyn<io::Error> =
+            TryUserRepositoryDyn::new(Box::new(storage));
+        repo.add_user(user(1)).unwrap();
+        repo.add_user(user(2)).unwrap();
+
+        let mut taken = user(2);
+        taken.email = "USER1@example.com".into();
+        assert!(matches!(
+            repo.update_user(taken.clone()),
+            Err(TryRepositoryError::Repository(RepositoryError::EmailTaken(
+                _
+            )))
+        ));
+        taken.id = 3;
+        assert!(matches!(
+            repo.add_user(taken),
+            Err(TryRepositoryError::Repository(RepositoryError::EmailTaken(
+                _
+            )))
+        ));
+        assert_eq!(repo.get_user(3).unwrap(), None);
+    }
+
+    #[test]
+    fn test_dynamic_dispatch_with_file_storage() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = FileStorage::open(dir.path().join("users.log")).unwrap();
+        let mut repo: TryUserRepositoryDyn<io::Error> =
+            TryUserRepositoryDyn::new(Box::new(storage));
+
+        repo.add_user(user(1)).unwrap();
+        assert_eq!(repo.get_user(1).unwrap().as_deref(), Some(&user(1)));
+    }
+
+    #[test]
+    fn test_static_dispatch_surfaces_errors() {
+        let mut repo = TryUserRepositoryStatic::new(SqliteStorage::open_in_memory().unwrap());
+
+        // `u64` ids beyond `i64::MAX` can't be stored in SQLite.
+        assert!(matches!(
+            repo.add_user(user(u64::MAX)),
+            Err(TryRepositoryError::Storage(_))
+        ));
+
+        repo.add_user(user(2)).unwrap();
+        assert_eq!(repo.get_user(2).unwrap().as_deref(), Some(&user(2)));
+    }
+
+    // Storage failing to store the `u64::MAX` key
+    struct Flaky(HashMapStorage<u64, User>);
+
+    impl TryStorage<u64, User> for Flaky {
+        type Error = RepositoryError;
+
+        fn try_set(&mut self, key: u64, val: User) -> Result<(), Self::Error> {
+            if key == u64::MAX {
+                return Err(RepositoryError::NotFound(key));
+            }
+            self.0.set(key, val);
+            Ok(())
+        }
+
+        fn try_get(&self, key: &u64) -> Result<Option<Cow<'_, User>>, Self::Error> {
+            Ok(self.0.get(key))
+        }
+
+        fn try_remove(&mut self, key: &u64) -> Result<Option<User>, Self::Error> {
+            Ok(self.0.remove(key))
+        }
+
+        fn try_iter(&self) -> Result<TryStorageIter<'_, u64, User>, Self::Error> {
+            Ok(self.0.iter())
+        }
+    }
+
+    #[test]
+    fn test_apply_batch_undoes_applied_writes() {
+        let mut storage = Flaky(HashMapStorage::new());
+        storage.try_set(1, user(1)).unwrap();
+        storage.try_set(2, user(2)).unwrap();
+
+        let writes = vec![
+            (1, None),
+            (2, Some(user(3))),
+            (3, Some(user(3))),
+            (u64::MAX, Some(user(u64::MAX))),
+        ];
+        assert_eq!(
+            storage.apply_batch(writes),
+            Err(RepositoryError::NotFound(u64::MAX))
+        );
+        assert_eq!(storage.try_get(&1).unwrap().as_deref(), Some(&user(1)));
+        assert_eq!(storage.try_get(&2).unwrap().as_deref(), Some(&user(2)));
+        assert_eq!(storage.try_get(&3).unwrap(), None);
+    }
+}
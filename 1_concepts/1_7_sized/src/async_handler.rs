@@ -0,0 +1,549 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::future::{self, Future};
+use std::hash::{BuildHasher, Hash, RandomState};
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Poll, Waker};
+use std::time::{Duration, Instant};
+use std::{fmt, mem};
+
+use task_1_6::User;
+
+use crate::{
+    validate_email, ActivateUser, ChangeEmail, CreateUser, DeactivateUser, DeleteUser, UserError,
+    UserRepository,
+};
+
+// Boxed future returned by AsyncUserRepository methods, so the trait stays
+// object safe
+pub type RepositoryFuture<'a, T> = Pin<Box<dyn Future<Output = T> + 'a>>;
+
+// Asynchronous counterpart of the UserRepository trait
+//
+// Reads are fallible as well, as async repositories are usually backed by I/O.
+pub trait AsyncUserRepository {
+    fn add_user(&mut self, user: User) -> RepositoryFuture<'_, Result<(), UserError>>;
+    fn get_user(&self, id: u64) -> RepositoryFuture<'_, Result<Option<User>, UserError>>;
+    fn update_user(&mut self, user: User) -> RepositoryFuture<'_, Result<(), UserError>>;
+    fn remove_user(&mut self, id: u64) -> RepositoryFuture<'_, Result<Option<User>, UserError>>;
+    fn find_by_email<'a>(
+        &'a self,
+        email: &'a str,
+    ) -> RepositoryFuture<'a, Result<Option<User>, UserError>>;
+}
+
+// Every blocking UserRepository is usable as an always ready AsyncUserRepository.
+impl<R: UserRepository + ?Sized> AsyncUserRepository for R {
+    fn add_user(&mut self, user: User) -> RepositoryFuture<'_, Result<(), UserError>> {
+        Box::pin(future::ready(UserRepository::add_user(self, user)))
+    }
+
+    fn get_user(&self, id: u64) -> RepositoryFuture<'_, Result<Option<User>, UserError>> {
+        let user = UserRepository::get_user(self, id).map(Cow::into_owned);
+        Box::pin(future::ready(Ok(user)))
+    }
+
+    fn update_user(&mut self, user: User) -> RepositoryFuture<'_, Result<(), UserError>> {
+        Box::pin(future::ready(UserRepository::update_user(self, user)))
+    }
+
+    fn remove_user(&mut self, id: u64) -> RepositoryFuture<'_, Result<Option<User>, UserError>> {
+        Box::pin(future::ready(Ok(UserRepository::remove_user(self, id))))
+    }
+
+    fn find_by_email<'a>(
+        &'a self,
+        email: &'a str,
+    ) -> RepositoryFuture<'a, Result<Option<User>, UserError>> {
+        let user = UserRepository::find_by_email(self, email).map(Cow::into_owned);
+        Box::pin(future::ready(Ok(user)))
+    }
+}
+
+// Asynchronous counterpart of the CommandHandler trait
+pub trait AsyncCommandHandler<C: ?Sized> {
+    type Context: ?Sized;
+    type Result;
+
+    fn handle_command(
+        &self,
+        cmd: &C,
+        ctx: &mut Self::Context,
+    ) -> impl Future<Output = Self::Result>;
+}
+
+// Checks the email isn't used by any user other than `id`.
+async fn ensure_email_free(
+    ctx: &dyn AsyncUserRepository,
+    id: u64,
+    email: &str,
+) -> Result<(), UserError> {
+    match ctx.find_by_email(email).await? {
+        Some(other) if other.id != id => Err(UserError::EmailTaken(email.to_owned().into())),
+        _ => Ok(()),
+    }
+}
+
+// New users are deactivated until an `ActivateUser` command.
+impl AsyncCommandHandler<CreateUser> for User {
+    type Context = dyn AsyncUserRepository;
+    type Result = Result<(), UserError>;
+
+    async fn handle_command(&self, cmd: &CreateUser, ctx: &mut Self::Context) -> Self::Result {
+        if ctx.get_user(cmd.id).await?.is_some() {
+            return Err(UserError::AlreadyExists(cmd.id));
+        }
+        validate_email(&cmd.email)?;
+        ensure_email_free(ctx, cmd.id, &cmd.email).await?;
+        let user = User {
+            id: cmd.id,
+            email: cmd.email.clone(),
+            activated: false,
+        };
+        ctx.add_user(user).await
+    }
+}
+
+impl AsyncCommandHandler<ActivateUser> for User {
+    type Context = dyn AsyncUserRepository;
+    type Result = Result<(), UserError>;
+
+    async fn handle_command(&self, cmd: &ActivateUser, ctx: &mut Self::Context) -> Self::Result {
+        set_activated(ctx, cmd.id, true).await
+    }
+}
+
+impl AsyncCommandHandler<DeactivateUser> for User {
+    type Context = dyn AsyncUserRepository;
+    type Result = Result<(), UserError>;
+
+    async fn handle_command(&self, cmd: &DeactivateUser, ctx: &mut Self::Context) -> Self::Result {
+        set_activated(ctx, cmd.id, false).await
+    }
+}
+
+async fn set_activated(
+    ctx: &mut dyn AsyncUserRepository,
+    id: u64,
+    activated: bool,
+) -> Result<(), UserError> {
+    let user = ctx.get_user(id).await?.ok_or(UserError::NotFound(id))?;
+    if user.activated == activated {
+        return Ok(());
+    }
+    ctx.update_user(User { activated, ..user }).await
+}
+
+impl AsyncCommandHandler<ChangeEmail> for User {
+    type Context = dyn AsyncUserRepository;
+    type Result = Result<(), UserError>;
+
+    async fn handle_command(&self, cmd: &ChangeEmail, ctx: &mut Self::Context) -> Self::Result {
+        let user = ctx
+            .get_user(cmd.id)
+            .await?
+            .ok_or(UserError::NotFound(cmd.id))?;
+        validate_email(&cmd.email)?;
+        ensure_email_free(ctx, cmd.id, &cmd.email).await?;
+        let user = User {
+            email: cmd.email.clone(),
+            ..user
+        };
+        ctx.update_user(user).await
+    }
+}
+
+impl AsyncCommandHandler<DeleteUser> for User {
+    type Context = dyn AsyncUserRepository;
+    type Result = Result<(), UserError>;
+
+    async fn handle_command(&self, cmd: &DeleteUser, ctx: &mut Self::Context) -> Self::Result {
+        ctx.remove_user(cmd.id)
+            .await?
+            .map(drop)
+            .ok_or(UserError::NotFound(cmd.id))
+    }
+}
+
+// Command optionally carrying a client-provided idempotency key
+pub struct Idempotent<C> {
+    pub key: Option<Cow<'static, str>>,
+    pub cmd: C,
+}
+
+// Error of re-submitting an idempotency key with another command than the
+// one it was first used with
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyReused(pub Cow<'static, str>);
+
+impl fmt::Display for KeyReused {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "idempotency key {} was used for another command", self.0)
+    }
+}
+
+// Handler remembering successful results by idempotency key, so re-submitted
+// commands return them instead of being executed again.
+//
+// The key is reserved before the command is executed, so a duplicate
+// submitted meanwhile waits for the outcome of the first one. Failed commands
+// aren't remembered and may be retried with the same key. Results are
+// forgotten once their TTL elapses.
+//
+// Keys are bound to the hash of their command, so a key re-submitted with
+// another command fails with `KeyReused` instead of returning a result that
+// doesn't belong to it.
+pub struct IdempotentHandler<H, T> {
+    inner: H,
+    ttl: Duration,
+    hasher: RandomState,
+    results: Slots<T>,
+}
+
+// Slots of the idempotency keys, along with the hash of their command
+type Slots<T> = Mutex<HashMap<Cow<'static, str>, (u64, Slot<T>)>>;
+
+// State of an idempotency key
+enum Slot<T> {
+    // The command is being executed, and these tasks wait for its outcome.
+    InFlight(Vec<Waker>),
+    // The command succeeded with the result at the instant.
+    Done(T, Instant),
+}
+
+// Outcome of reserving an idempotency key
+enum Reserved<'a, T> {
+    // The command has already succeeded with the result.
+    Done(T),
+    // The command is to be executed, and its result recorded in the reservation.
+    New(Reservation<'a, T>),
+    // The key is bound to another command.
+    Reused,
+}
+
+impl<H, T> IdempotentHandler<H, T> {
+    // One day, as retries come within minutes usually.
+    pub const DEFAULT_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+    pub fn new(inner: H) -> Self {
+        Self {
+            inner,
+            ttl: Self::DEFAULT_TTL,
+            hasher: RandomState::new(),
+            results: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Sets for how long results are remembered.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    // Resolves to the remembered result of the `key`, or to a reservation of
+    // the `key` if there is none, unless it's bound to another `hash`.
+    async fn reserve<'a>(&'a self, key: &'a str, hash: u64) -> Reserved<'a, T>
+    where
+        T: Clone,
+    {
+        future::poll_fn(|cx| {
+            let mut results = self.results.lock().unwrap();
+            let now = Instant::now();
+            results.retain(|_, (_, slot)| match slot {
+                Slot::InFlight(_) => true,
+                Slot::Done(_, at) => now.duration_since(*at) < self.ttl,
+            });
+            match results.get_mut(key) {
+                Some((bound, _)) if *bound != hash => Poll::Ready(Reserved::Reused),
+                Some((_, Slot::Done(res, _))) => Poll::Ready(Reserved::Done(res.clone())),
+                Some((_, Slot::InFlight(waiters))) => {
+                    waiters.push(cx.waker().clone());
+                    Poll::Pending
+                }
+                None => {
+                    let slot = (hash, Slot::InFlight(Vec::new()));
+                    results.insert(Cow::Owned(key.to_owned()), slot);
+                    Poll::Ready(Reserved::New(Reservation {
+                        results: &self.results,
+                        key,
+                        res: None,
+                    }))
+                }
+            }
+        })
+        .await
+    }
+}
+
+// Reserved idempotency key, released on drop
+//
+// The key gets the result if there is one, and is freed otherwise, so
+// neither failed nor cancelled commands leave their waiters hanging.
+struct Reservation<'a, T> {
+    results: &'a Slots<T>,
+    key: &'a str,
+    res: Option<T>,
+}
+
+impl<T> Drop for Reservation<'_, T> {
+    fn drop(&mut self) {
+        let mut results = self.results.lock().unwrap();
+        let slot = match self.res.take() {
+            Some(res) => results
+                .get_mut(self.key)
+                .map(|(_, slot)| mem::replace(slot, Slot::Done(res, Instant::now()))),
+            None => results.remove(self.key).map(|(_, slot)| slot),
+        };
+        drop(results);
+        if let Some(Slot::InFlight(waiters)) = slot {
+            waiters.into_iter().for_each(Waker::wake);
+        }
+    }
+}
+
+impl<C, H, T, E> AsyncCommandHandler<Idempotent<C>> for IdempotentHandler<H, T>
+where
+    C: Hash,
+    H: AsyncCommandHandler<C, Result = Result<T, E>>,
+    T: Clone,
+    E: From<KeyReused>,
+{
+    type Context = H::Context;
+    type Result = Result<T, E>;
+
+    async fn handle_command(&self, cmd: &Idempotent<C>, ctx: &mut Self::Context) -> Self::Result {
+        let Some(key) = &cmd.key else {
+            return self.inner.handle_command(&cmd.cmd, ctx).await;
+        };
+        let mut reservation = match self.reserve(key, self.hasher.hash_one(&cmd.cmd)).await {
+            Reserved::Done(res) => return Ok(res),
+            Reserved::New(reservation) => reservation,
+            Reserved::Reused => return Err(KeyReused(key.clone()).into()),
+        };
+        let res = self.inner.handle_command(&cmd.cmd, ctx).await?;
+        reservation.res = Some(res.clone());
+        Ok(res)
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use task_1_6::{AsyncAdapter, AsyncUserRepositoryStatic, SqliteStorage};
+
+    use super::*;
+    use crate::MockUserRepository;
+
+    // Handler yielding once while counting its executions
+    #[derive(Default)]
+    struct Counting(AtomicUsize);
+
+    impl AsyncCommandHandler<()> for Counting {
+        type Context = ();
+        type Result = Result<usize, UserError>;
+
+        async fn handle_command(&self, _: &(), _: &mut ()) -> Self::Result {
+            let count = self.0.fetch_add(1, Ordering::SeqCst) + 1;
+            tokio::task::yield_now().await;
+            Ok(count)
+        }
+    }
+
+    fn handler() -> User {
+        User {
+            id: 0,
+            email: Cow::Borrowed("placeholder"),
+            activated: false,
+        }
+    }
+
+    fn create_user(
+        key: Option<&'static str>,
+        id: u64,
+        email: &'static str,
+    ) -> Idempotent<CreateUser> {
+        Idempotent {
+            key: key.map(Cow::Borrowed),
+            cmd: CreateUser {
+                id,
+                email: Cow::Borrowed(email),
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn test_async_create_user() {
+        let mut repo = MockUserRepository::new();
+        let ctx: &mut dyn AsyncUserRepository = &mut repo;
+        let cmd = CreateUser {
+            id: 1,
+            email: Cow::Borrowed("one@example.com"),
+        };
+
+        assert_eq!(handler().handle_command(&cmd, ctx).await, Ok(()));
+        assert_eq!(
+            handler().handle_command(&cmd, ctx).await,
+            Err(UserError::AlreadyExists(1))
+        );
+        assert!(ctx.get_user(1).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_async_commands_over_async_storage() {
+        let storage = AsyncAdapter::new(SqliteStorage::open_in_memory().unwrap());
+        let mut repo = AsyncUserRepositoryStatic::new(storage);
+        let ctx: &mut dyn AsyncUserRepository = &mut repo;
+        for (id, email) in [(1, "one@example.com"), (2, "two@example.com")] {
+            let cmd = CreateUser {
+                id,
+                email: Cow::Borrowed(email),
+            };
+            handler().handle_command(&cmd, ctx).await.unwrap();
+        }
+
+        handler()
+            .handle_command(&ActivateUser { id: 1 }, ctx)
+            .await
+            .unwrap();
+        assert!(ctx.get_user(1).await.unwrap().unwrap().activated);
+        handler()
+            .handle_command(&DeactivateUser { id: 1 }, ctx)
+            .await
+            .unwrap();
+        assert!(!ctx.get_user(1).await.unwrap().unwrap().activated);
+
+        let change = |email| ChangeEmail {
+            id: 1,
+            email: Cow::Borrowed(email),
+        };
+        assert_eq!(
+            handler()
+                .handle_command(&change("TWO@example.com"), ctx)
+                .await,
+            Err(UserError::EmailTaken(Cow::Borrowed("TWO@example.com")))
+        );
+        handler()
+            .handle_command(&change("uno@example.com"), ctx)
+            .await
+            .unwrap();
+        assert_eq!(
+            ctx.get_user(1).await.unwrap().unwrap().email,
+            "uno@example.com"
+        );
+
+        handler()
+            .handle_command(&DeleteUser { id: 1 }, ctx)
+            .await
+            .unwrap();
+        assert_eq!(
+            handler().handle_command(&DeleteUser { id: 1 }, ctx).await,
+            Err(UserError::NotFound(1))
+        );
+        assert_eq!(
+            handler().handle_command(&ActivateUser { id: 1 }, ctx).await,
+            Err(UserError::NotFound(1))
+        );
+
+        // `u64` ids beyond `i64::MAX` can't be stored in SQLite.
+        let cmd = CreateUser {
+            id: u64::MAX,
+            email: Cow::Borrowed("max@example.com"),
+        };
+        assert!(matches!(
+            handler().handle_command(&cmd, ctx).await,
+            Err(UserError::Storage(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_retried_command_returns_stored_result() {
+        let handler = IdempotentHandler::new(handler());
+        let mut repo = MockUserRepository::new();
+
+        let cmd = create_user(Some("req-1"), 1, "one@example.com");
+        assert_eq!(handler.handle_command(&cmd, &mut repo).await, Ok(()));
+        assert_eq!(handler.handle_command(&cmd, &mut repo).await, Ok(()));
+        assert_eq!(repo.users.len(), 1);
+
+        let cmd = create_user(Some("req-2"), 1, "one@example.com");
+        assert_eq!(
+            handler.handle_command(&cmd, &mut repo).await,
+            Err(UserError::AlreadyExists(1))
+        );
+        let cmd = create_user(None, 1, "one@example.com");
+        assert_eq!(
+            handler.handle_command(&cmd, &mut repo).await,
+            Err(UserError::AlreadyExists(1))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reused_key_is_rejected() {
+        let handler = IdempotentHandler::new(handler());
+        let mut repo = MockUserRepository::new();
+
+        let cmd = create_user(Some("req-1"), 1, "one@example.com");
+        assert_eq!(handler.handle_command(&cmd, &mut repo).await, Ok(()));
+
+        let other = create_user(Some("req-1"), 2, "two@example.com");
+        assert_eq!(
+            handler.handle_command(&other, &mut repo).await,
+            Err(UserError::KeyReused(KeyReused(Cow::Borrowed("req-1"))))
+        );
+        assert_eq!(repo.users.len(), 1);
+        assert_eq!(handler.handle_command(&cmd, &mut repo).await, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn test_failures_are_not_stored() {
+        let handler = IdempotentHandler::new(handler());
+        let mut repo = MockUserRepository::new();
+        handler
+            .handle_command(&create_user(None, 1, "one@example.com"), &mut repo)
+            .await
+            .unwrap();
+
+        let cmd = create_user(Some("req-1"), 2, "one@example.com");
+        assert_eq!(
+            handler.handle_command(&cmd, &mut repo).await,
+            Err(UserError::EmailTaken(Cow::Borrowed("one@example.com")))
+        );
+
+        UserRepository::remove_user(&mut repo, 1);
+        assert_eq!(handler.handle_command(&cmd, &mut repo).await, Ok(()));
+        assert!(UserRepository::get_user(&repo, 2).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_duplicates_wait_for_first() {
+        let handler = IdempotentHandler::new(Counting::default());
+        let cmd = Idempotent {
+            key: Some(Cow::Borrowed("req-1")),
+            cmd: (),
+        };
+
+        let (mut one, mut other) = ((), ());
+        let results = tokio::join!(
+            handler.handle_command(&cmd, &mut one),
+            handler.handle_command(&cmd, &mut other),
+        );
+        assert_eq!(results, (Ok(1), Ok(1)));
+        assert_eq!(handler.inner.0.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_results_expire() {
+        let handler = IdempotentHandler::new(Counting::default()).with_ttl(Duration::ZERO);
+        let cmd = Idempotent {
+            key: Some(Cow::Borrowed("req-1")),
+            cmd: (),
+        };
+
+        assert_eq!(handler.handle_command(&cmd, &mut ()).await, Ok(1));
+        assert_eq!(handler.handle_command(&cmd, &mut ()).await, Ok(2));
+        assert!(handler.results.lock().unwrap().len() <= 1);
+    }
+}
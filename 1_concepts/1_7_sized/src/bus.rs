@@ -0,0 +1,435 @@
+use std::any::{self, Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::CommandHandler;
+
+// Command which can be dispatched through the CommandBus
+pub trait Command: Any {
+    fn name(&self) -> &'static str {
+        any::type_name::<Self>()
+    }
+}
+
+// Error of dispatching a command with no registered handler
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UnhandledCommand(pub &'static str);
+
+impl fmt::Display for UnhandledCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no handler registered for {}", self.0)
+    }
+}
+
+// Type-erased handler of a single command type
+type ErasedHandler<Ctx, T, E> = Box<dyn Fn(&dyn Command, &mut Ctx) -> Result<T, E>>;
+
+// Hook running around every dispatch of a CommandBus
+//
+// Implementations decide whether (and how many times) to call the rest of the
+// chain via `next`.
+pub trait Middleware<Ctx: ?Sized, T, E> {
+    fn handle(&self, cmd: &dyn Command, ctx: &mut Ctx, next: Next<'_, Ctx, T, E>) -> Result<T, E>;
+}
+
+// Rest of the middleware chain, ending with the command handler
+pub struct Next<'a, Ctx: ?Sized, T, E> {
+    middleware: &'a [Box<dyn Middleware<Ctx, T, E>>],
+    handler: &'a dyn Fn(&dyn Command, &mut Ctx) -> Result<T, E>,
+}
+
+impl<Ctx: ?Sized, T, E> Clone for Next<'_, Ctx, T, E> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<Ctx: ?Sized, T, E> Copy for Next<'_, Ctx, T, E> {}
+
+impl<Ctx: ?Sized, T, E> Next<'_, Ctx, T, E> {
+    pub fn run(self, cmd: &dyn Command, ctx: &mut Ctx) -> Result<T, E> {
+        match self.middleware.split_first() {
+            Some((first, rest)) => first.handle(
+                cmd,
+                ctx,
+                Next {
+                    middleware: rest,
+                    handler: self.handler,
+                },
+            ),
+            None => (self.handler)(cmd, ctx),
+        }
+    }
+}
+
+// Dispatcher routing commands to the handlers registered for their types
+//
+// All the handlers share the same `Context` and result types. Middleware runs
+// in the order it was added, the first one being the outermost.
+pub struct CommandBus<Ctx: ?Sized, T, E> {
+    handlers: HashMap<TypeId, ErasedHandler<Ctx, T, E>>,
+    middleware: Vec<Box<dyn Middleware<Ctx, T, E>>>,
+}
+
+impl<Ctx: ?Sized, T, E> Default for CommandBus<Ctx, T, E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Ctx: ?Sized, T, E> CommandBus<Ctx, T, E> {
+    pub fn new() -> Self {
+        Self {
+            handlers: HashMap::new(),
+            middleware: Vec::new(),
+        }
+    }
+
+    // Registers `handler` for commands of type `C`, replacing the previous one.
+    pub fn register<C, H>(&mut self, handler: H) -> &mut Self
+    where
+        C: Command,
+        H: CommandHandler<C, Context = Ctx, Result = Result<T, E>> + 'static,
+    {
+        let handler = move |cmd: &dyn Command, ctx: &mut Ctx| {
+            let cmd = (cmd as &dyn Any)
+                .downcast_ref::<C>()
+                .expect("handlers are registered by command `TypeId`");
+            handler.handle_command(cmd, ctx)
+        };
+        self.handlers.insert(TypeId::of::<C>(), Box::new(handler));
+        self
+    }
+
+    pub fn with(&mut self, middleware: impl Middleware<Ctx, T, E> + 'static) -> &mut Self {
+        self.middleware.push(Box::new(middleware));
+        self
+    }
+
+    pub fn dispatch<C: Command>(&self, cmd: &C, ctx: &mut Ctx) -> Result<T, E>
+    where
+        E: From<UnhandledCommand>,
+    {
+        let handler = self
+            .handlers
+            .get(&TypeId::of::<C>())
+            .ok_or_else(|| UnhandledCommand(cmd.name()))?;
+        Next {
+            middleware: &self.middleware,
+            handler: &**handler,
+        }
+        .run(cmd, ctx)
+    }
+}
+
+// Middleware reporting every dispatched command and its outcome
+pub struct Logging<F> {
+    log: F,
+}
+
+impl<F: Fn(String)> Logging<F> {
+    pub fn new(log: F) -> Self {
+        Self { log }
+    }
+}
+
+impl<Ctx: ?Sized, T, E: fmt::Display, F: Fn(String)> Middleware<Ctx, T, E> for Logging<F> {
+    fn handle(&self, cmd: &dyn Command, ctx: &mut Ctx, next: Next<'_, Ctx, T, E>) -> Result<T, E> {
+        (self.log)(format!("dispatching {}", cmd.name()));
+        let res = next.run(cmd, ctx);
+        match &res {
+            Ok(_) => (self.log)(format!("{} succeeded", cmd.name())),
+            Err(e) => (self.log)(format!("{} failed: {e}", cmd.name())),
+        }
+        res
+    }
+}
+
+// Type-erased validation rule of a single command type
+type Rule<E> = Box<dyn Fn(&dyn Command) -> Result<(), E>>;
+
+// Middleware rejecting commands before they reach their handlers
+pub struct Validation<E> {
+    rules: HashMap<TypeId, Vec<Rule<E>>>,
+}
+
+impl<E> Default for Validation<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E> Validation<E> {
+    pub fn new() -> Self {
+        Self {
+            rules: HashMap::new(),
+        }
+    }
+
+    // Adds a rule checked for every command of type `C`.
+    pub fn rule<C: Command>(mut self, rule: impl Fn(&C) -> Result<(), E> + 'static) -> Self {
+        let rule = move |cmd: &dyn Command| {
+            let cmd = (cmd as &dyn Any)
+                .downcast_ref::<C>()
+                .expect("rules are registered by command `TypeId`");
+            rule(cmd)
+        };
+        self.rules
+            .entry(TypeId::of::<C>())
+            .or_default()
+            .push(Box::new(rule));
+        self
+    }
+}
+
+impl<Ctx: ?Sized, T, E> Middleware<Ctx, T, E> for Validation<E> {
+    fn handle(&self, cmd: &dyn Command, ctx: &mut Ctx, next: Next<'_, Ctx, T, E>) -> Result<T, E> {
+        let type_id = (cmd as &dyn Any).type_id();
+        for rule in self.rules.get(&type_id).into_iter().flatten() {
+            rule(cmd)?;
+        }
+        next.run(cmd, ctx)
+    }
+}
+
+// Middleware measuring how long every dispatch takes
+pub struct Timing<F> {
+    report: F,
+}
+
+impl<F: Fn(&'static str, Duration)> Timing<F> {
+    pub fn new(report: F) -> Self {
+        Self { report }
+    }
+}
+
+impl<Ctx: ?Sized, T, E, F: Fn(&'static str, Duration)> Middleware<Ctx, T, E> for Timing<F> {
+    fn handle(&self, cmd: &dyn Command, ctx: &mut Ctx, next: Next<'_, Ctx, T, E>) -> Result<T, E> {
+        let start = Instant::now();
+        let res = next.run(cmd, ctx);
+        (self.report)(cmd.name(), start.elapsed());
+        res
+    }
+}
+
+// Middleware re-running the rest of the chain on retryable errors
+pub struct Retry<F> {
+    max_attempts: usize,
+    is_retryable: F,
+}
+
+impl<F> Retry<F> {
+    // `max_attempts` includes the first one.
+    pub fn new<E>(max_attempts: usize, is_retryable: F) -> Self
+    where
+        F: Fn(&E) -> bool,
+    {
+        Self {
+            max_attempts: max_attempts.max(1),
+            is_retryable,
+        }
+    }
+}
+
+impl<Ctx: ?Sized, T, E, F: Fn(&E) -> bool> Middleware<Ctx, T, E> for Retry<F> {
+    fn handle(&self, cmd: &dyn Command, ctx: &mut Ctx, next: Next<'_, Ctx, T, E>) -> Result<T, E> {
+        let mut attempt = 1;
+        loop {
+            match next.run(cmd, ctx) {
+                Err(e) if attempt < self.max_attempts && (self.is_retryable)(&e) => attempt += 1,
+                res => return res,
+            }
+        }
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use std::borrow::Cow;
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::{
+        ActivateUser, ChangeEmail, CreateUser, DeactivateUser, DeleteUser, MockUserRepository,
+        User, UserError, UserRepository,
+    };
+
+    // Command failing the given number of times before succeeding
+    struct Flaky {
+        failures: Cell<usize>,
+    }
+
+    impl Command for Flaky {}
+
+    impl CommandHandler<Flaky> for User {
+        type Context = dyn UserRepository;
+        type Result = Result<(), UserError>;
+
+        fn handle_command(&self, cmd: &Flaky, _: &mut Self::Context) -> Self::Result {
+            match cmd.failures.get() {
+                0 => Ok(()),
+                n => {
+                    cmd.failures.set(n - 1);
+                    Err(UserError::NotFound(0))
+                }
+            }
+        }
+    }
+
+    fn handler() -> User {
+        User {
+            id: 0,
+            email: Cow::Borrowed("placeholder"),
+            activated: false,
+        }
+    }
+
+    fn bus() -> CommandBus<dyn UserRepository, (), UserError> {
+        let mut bus = CommandBus::new();
+        bus.register::<CreateUser, _>(handler())
+            .register::<ActivateUser, _>(handler())
+            .register::<DeactivateUser, _>(handler())
+            .register::<ChangeEmail, _>(handler())
+            .register::<DeleteUser, _>(handler())
+            .register::<Flaky, _>(handler());
+        bus
+    }
+
+    fn create_user(id: u64, email: &'static str) -> CreateUser {
+        CreateUser {
+            id,
+            email: Cow::Borrowed(email),
+        }
+    }
+
+    #[test]
+    fn test_dispatches_by_command_type() {
+        let bus = bus();
+        let mut repo = MockUserRepository::new();
+
+        bus.dispatch(&create_user(1, "a@example.com"), &mut repo)
+            .unwrap();
+        bus.dispatch(&ActivateUser { id: 1 }, &mut repo).unwrap();
+        assert!(repo.get_user(1).unwrap().activated);
+
+        let cmd = ChangeEmail {
+            id: 1,
+            email: Cow::Borrowed("b@example.com"),
+        };
+        bus.dispatch(&cmd, &mut repo).unwrap();
+        bus.dispatch(&DeactivateUser { id: 1 }, &mut repo).unwrap();
+        let user = repo.get_user(1).unwrap();
+        assert_eq!(user.email, "b@example.com");
+        assert!(!user.activated);
+
+        bus.dispatch(&DeleteUser { id: 1 }, &mut repo).unwrap();
+        assert!(repo.get_user(1).is_none());
+    }
+
+    #[test]
+    fn test_unhandled_command() {
+        struct Unknown;
+        impl Command for Unknown {}
+
+        let bus = bus();
+        let res = bus.dispatch(&Unknown, &mut MockUserRepository::new());
+        assert_eq!(
+            res,
+            Err(UserError::Unhandled(UnhandledCommand(any::type_name::<
+                Unknown,
+            >())))
+        );
+    }
+
+    #[test]
+    fn test_middleware_is_shared_by_command_types() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut bus = bus();
+        bus.with(Logging::new({
+            let log = Rc::clone(&log);
+            move |line| log.borrow_mut().push(line)
+        }));
+        let mut repo = MockUserRepository::new();
+
+        bus.dispatch(&create_user(1, "a@example.com"), &mut repo)
+            .unwrap();
+        bus.dispatch(&ActivateUser { id: 1 }, &mut repo).unwrap();
+        let cmd = ChangeEmail {
+            id: 1,
+            email: Cow::Borrowed("b@example.com"),
+        };
+        bus.dispatch(&cmd, &mut repo).unwrap();
+        bus.dispatch(&DeleteUser { id: 1 }, &mut repo).unwrap();
+
+        let log = log.borrow();
+        let dispatched: Vec<_> = log
+            .iter()
+            .filter_map(|line| line.strip_prefix("dispatching "))
+            .collect();
+        assert_eq!(
+            dispatched,
+            [
+                any::type_name::<CreateUser>(),
+                any::type_name::<ActivateUser>(),
+                any::type_name::<ChangeEmail>(),
+                any::type_name::<DeleteUser>(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_middleware_chain() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let timings = Rc::new(Cell::new(0));
+
+        let mut bus = bus();
+        bus.with(Logging::new({
+            let log = Rc::clone(&log);
+            move |line| log.borrow_mut().push(line)
+        }))
+        .with(Timing::new({
+            let timings = Rc::clone(&timings);
+            move |_, _| timings.set(timings.get() + 1)
+        }))
+        .with(Validation::new().rule(|cmd: &CreateUser| {
+            if cmd.email.ends_with("@example.com") {
+                Ok(())
+            } else {
+                Err(UserError::InvalidEmail(cmd.email.clone()))
+            }
+        }))
+        .with(Retry::new(3, |e: &UserError| {
+            matches!(e, UserError::NotFound(0))
+        }));
+        let mut repo = MockUserRepository::new();
+
+        let res = bus.dispatch(&create_user(1, "a@example.org"), &mut repo);
+        assert_eq!(
+            res,
+            Err(UserError::InvalidEmail(Cow::Borrowed("a@example.org")))
+        );
+        assert!(repo.get_user(1).is_none());
+
+        let flaky = Flaky {
+            failures: Cell::new(2),
+        };
+        assert_eq!(bus.dispatch(&flaky, &mut repo), Ok(()));
+
+        let flaky = Flaky {
+            failures: Cell::new(3),
+        };
+        assert_eq!(bus.dispatch(&flaky, &mut repo), Err(UserError::NotFound(0)));
+
+        assert_eq!(timings.get(), 3);
+        let name = any::type_name::<CreateUser>();
+        assert_eq!(log.borrow()[0], format!("dispatching {name}"));
+        assert_eq!(
+            log.borrow()[1],
+            format!("{name} failed: email a@example.org is invalid")
+        );
+        assert!(log.borrow()[3].ends_with("Flaky succeeded"));
+    }
+}
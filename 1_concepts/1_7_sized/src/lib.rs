@@ -0,0 +1,438 @@
+use std::borrow::Cow;
+use std::{error, fmt};
+use task_1_6::{RepositoryError, User};
+
+pub mod async_handler;
+pub mod bus;
+pub mod repository;
+
+pub use self::async_handler::KeyReused;
+pub use self::bus::{
+    Command, CommandBus, Logging, Middleware, Next, Retry, Timing, UnhandledCommand, Validation,
+};
+
+// Command structure
+//
+// Commands are hashed to tell whether a re-submitted idempotency key comes
+// with the same one.
+#[derive(Hash)]
+pub struct CreateUser {
+    pub id: u64,
+    pub email: Cow<'static, str>,
+}
+
+impl Command for CreateUser {}
+
+#[derive(Hash)]
+pub struct ActivateUser {
+    pub id: u64,
+}
+
+impl Command for ActivateUser {}
+
+#[derive(Hash)]
+pub struct DeactivateUser {
+    pub id: u64,
+}
+
+impl Command for DeactivateUser {}
+
+#[derive(Hash)]
+pub struct ChangeEmail {
+    pub id: u64,
+    pub email: Cow<'static, str>,
+}
+
+impl Command for ChangeEmail {}
+
+#[derive(Hash)]
+pub struct DeleteUser {
+    pub id: u64,
+}
+
+impl Command for DeleteUser {}
+
+// Errors of handling user commands
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum UserError {
+    AlreadyExists(u64),
+    NotFound(u64),
+    InvalidEmail(Cow<'static, str>),
+    EmailTaken(Cow<'static, str>),
+    Unhandled(UnhandledCommand),
+    KeyReused(KeyReused),
+    // The storage of an async repository failed, with the error message.
+    Storage(Cow<'static, str>),
+}
+
+impl fmt::Display for UserError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::AlreadyExists(id) => write!(f, "user {id} already exists"),
+            Self::NotFound(id) => write!(f, "user {id} not found"),
+            Self::InvalidEmail(email) => write!(f, "email {email} is invalid"),
+            Self::EmailTaken(email) => write!(f, "email {email} is already taken"),
+            Self::Unhandled(e) => e.fmt(f),
+            Self::KeyReused(e) => e.fmt(f),
+            Self::Storage(e) => write!(f, "storage failed: {e}"),
+        }
+    }
+}
+
+impl error::Error for UserError {}
+
+impl From<UnhandledCommand> for UserError {
+    fn from(e: UnhandledCommand) -> Self {
+        Self::Unhandled(e)
+    }
+}
+
+impl From<KeyReused> for UserError {
+    fn from(e: KeyReused) -> Self {
+        Self::KeyReused(e)
+    }
+}
+
+impl From<RepositoryError> for UserError {
+    fn from(e: RepositoryError) -> Self {
+        match e {
+            RepositoryError::AlreadyExists(id) => Self::AlreadyExists(id),
+            RepositoryError::NotFound(id) => Self::NotFound(id),
+            RepositoryError::EmailTaken(email) => Self::EmailTaken(email),
+        }
+    }
+}
+
+// UserRepository trait, implemented by the task_1_6 repositories and mocked
+// for testing purposes
+pub trait UserRepository {
+    fn add_user(&mut self, user: User) -> Result<(), UserError>;
+    fn get_user(&self, id: u64) -> Option<Cow<'_, User>>;
+    fn update_user(&mut self, user: User) -> Result<(), UserError>;
+    fn remove_user(&mut self, id: u64) -> Option<User>;
+    fn find_by_email(&self, email: &str) -> Option<Cow<'_, User>>;
+}
+
+// CommandHandler trait
+pub trait CommandHandler<C: ?Sized> {
+    type Context: ?Sized;
+    type Result;
+
+    fn handle_command(&self, cmd: &C, ctx: &mut Self::Context) -> Self::Result;
+}
+
+// Checks the email has a non-empty local part and a dotted domain.
+fn validate_email(email: &str) -> Result<(), UserError> {
+    let valid = match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty()
+                && !domain.contains('@')
+                && domain.split('.').count() > 1
+                && domain.split('.').all(|label| !label.is_empty())
+                && !email.contains(char::is_whitespace)
+        }
+        None => false,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(UserError::InvalidEmail(email.to_owned().into()))
+    }
+}
+
+// Checks the email isn't used by any user other than `id`.
+fn ensure_email_free(ctx: &dyn UserRepository, id: u64, email: &str) -> Result<(), UserError> {
+    match ctx.find_by_email(email) {
+        Some(other) if other.id != id => Err(UserError::EmailTaken(email.to_owned().into())),
+        _ => Ok(()),
+    }
+}
+
+// Implement CommandHandler for User and CreateUser
+//
+// New users are deactivated until an `ActivateUser` command.
+impl CommandHandler<CreateUser> for User {
+    type Context = dyn UserRepository;
+    type Result = Result<(), UserError>;
+
+    fn handle_command(&self, cmd: &CreateUser, ctx: &mut Self::Context) -> Self::Result {
+        if ctx.get_user(cmd.id).is_some() {
+            return Err(UserError::AlreadyExists(cmd.id));
+        }
+        validate_email(&cmd.email)?;
+        ensure_email_free(ctx, cmd.id, &cmd.email)?;
+        ctx.add_user(User {
+            id: cmd.id,
+            email: cmd.email.clone(),
+            activated: false,
+        })
+    }
+}
+
+impl CommandHandler<ActivateUser> for User {
+    type Context = dyn UserRepository;
+    type Result = Result<(), UserError>;
+
+    fn handle_command(&self, cmd: &ActivateUser, ctx: &mut Self::Context) -> Self::Result {
+        set_activated(ctx, cmd.id, true)
+    }
+}
+
+impl CommandHandler<DeactivateUser> for User {
+    type Context = dyn UserRepository;
+    type Result = Result<(), UserError>;
+
+    fn handle_command(&self, cmd: &DeactivateUser, ctx: &mut Self::Context) -> Self::Result {
+        set_activated(ctx, cmd.id, false)
+    }
+}
+
+fn set_activated(ctx: &mut dyn UserRepository, id: u64, activated: bool) -> Result<(), UserError> {
+    let user = ctx.get_user(id).ok_or(UserError::NotFound(id))?;
+    if user.activated == activated {
+        return Ok(());
+    }
+    let user = User {
+        activated,
+        ..user.into_owned()
+    };
+    ctx.update_user(user)
+}
+
+impl CommandHandler<ChangeEmail> for User {
+    type Context = dyn UserRepository;
+    type Result = Result<(), UserError>;
+
+    fn handle_command(&self, cmd: &ChangeEmail, ctx: &mut Self::Context) -> Self::Result {
+        let user = ctx.get_user(cmd.id).ok_or(UserError::NotFound(cmd.id))?;
+        let user = User {
+            email: cmd.email.clone(),
+            ..user.into_owned()
+        };
+        validate_email(&cmd.email)?;
+        ensure_email_free(ctx, cmd.id, &cmd.email)?;
+        ctx.update_user(user)
+    }
+}
+
+impl CommandHandler<DeleteUser> for User {
+    type Context = dyn UserRepository;
+    type Result = Result<(), UserError>;
+
+    fn handle_command(&self, cmd: &DeleteUser, ctx: &mut Self::Context) -> Self::Result {
+        ctx.remove_user(cmd.id)
+            .map(drop)
+            .ok_or(UserError::NotFound(cmd.id))
+    }
+}
+
+// Mock implementation of UserRepository for testing
+pub struct MockUserRepository {
+    users: Vec<User>,
+}
+
+impl Default for MockUserRepository {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockUserRepository {
+    pub fn new() -> Self {
+        Self { users: vec![] }
+    }
+}
+
+impl UserRepository for MockUserRepository {
+    fn add_user(&mut self, user: User) -> Result<(), UserError> {
+        self.users.push(user);
+        Ok(())
+    }
+
+    fn get_user(&self, id: u64) -> Option<Cow<'_, User>> {
+        self.users
+            .iter()
+            .find(|user| user.id == id)
+            .map(Cow::Borrowed)
+    }
+
+    fn update_user(&mut self, user: User) -> Result<(), UserError> {
+        let stored = self
+            .users
+            .iter_mut()
+            .find(|u| u.id == user.id)
+            .ok_or(UserError::NotFound(user.id))?;
+        *stored = user;
+        Ok(())
+    }
+
+    fn remove_user(&mut self, id: u64) -> Option<User> {
+        let pos = self.users.iter().position(|user| user.id == id)?;
+        Some(self.users.remove(pos))
+    }
+
+    fn find_by_email(&self, email: &str) -> Option<Cow<'_, User>> {
+        self.users
+            .iter()
+            .find(|user| user.email.eq_ignore_ascii_case(email))
+            .map(Cow::Borrowed)
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_handler_create_user() {
+        let mut mock_repo = MockUserRepository::new();
+        let user_repo: &mut dyn UserRepository = &mut mock_repo;
+
+        let cmd = CreateUser {
+            id: 1,
+            email: Cow::Borrowed("test@example.com"),
+        };
+
+        let handler = User {
+            id: 0,
+            email: Cow::Borrowed("placeholder"),
+            activated: false,
+        };
+
+        // Add a user successfully
+        let result = handler.handle_command(&cmd, user_repo);
+        assert!(result.is_ok());
+        assert!(user_repo.get_user(1).is_some());
+
+        // Try adding the same user again
+        let result = handler.handle_command(&cmd, user_repo);
+        assert_eq!(result, Err(UserError::AlreadyExists(1)));
+    }
+
+    fn handler() -> User {
+        User {
+            id: 0,
+            email: Cow::Borrowed("placeholder"),
+            activated: false,
+        }
+    }
+
+    fn repo_with_users() -> MockUserRepository {
+        let mut repo = MockUserRepository::new();
+        for (id, email) in [(1, "one@example.com"), (2, "two@example.com")] {
+            let cmd = CreateUser {
+                id,
+                email: Cow::Borrowed(email),
+            };
+            handler().handle_command(&cmd, &mut repo).unwrap();
+        }
+        repo
+    }
+
+    #[test]
+    fn test_create_user_validates_email() {
+        let mut repo = repo_with_users();
+
+        for email in [
+            "",
+            "one",
+            "@example.com",
+            "one@",
+            "one@example",
+            "o ne@example.com",
+        ] {
+            let cmd = CreateUser {
+                id: 3,
+                email: Cow::Borrowed(email),
+            };
+            assert_eq!(
+                handler().handle_command(&cmd, &mut repo),
+                Err(UserError::InvalidEmail(Cow::Borrowed(email)))
+            );
+        }
+
+        let cmd = CreateUser {
+            id: 3,
+            email: Cow::Borrowed("ONE@example.com"),
+        };
+        assert_eq!(
+            handler().handle_command(&cmd, &mut repo),
+            Err(UserError::EmailTaken(cmd.email.clone()))
+        );
+        assert!(repo.get_user(3).is_none());
+        assert!(!repo.get_user(1).unwrap().activated);
+    }
+
+    #[test]
+    fn test_activation() {
+        let mut repo = repo_with_users();
+
+        handler()
+            .handle_command(&ActivateUser { id: 1 }, &mut repo)
+            .unwrap();
+        assert!(repo.get_user(1).unwrap().activated);
+        handler()
+            .handle_command(&ActivateUser { id: 1 }, &mut repo)
+            .unwrap();
+        assert!(repo.get_user(1).unwrap().activated);
+
+        handler()
+            .handle_command(&DeactivateUser { id: 1 }, &mut repo)
+            .unwrap();
+        assert!(!repo.get_user(1).unwrap().activated);
+
+        assert_eq!(
+            handler().handle_command(&ActivateUser { id: 3 }, &mut repo),
+            Err(UserError::NotFound(3))
+        );
+        assert_eq!(
+            handler().handle_command(&DeactivateUser { id: 3 }, &mut repo),
+            Err(UserError::NotFound(3))
+        );
+    }
+
+    #[test]
+    fn test_change_email() {
+        let mut repo = repo_with_users();
+        let change = |id, email| ChangeEmail {
+            id,
+            email: Cow::Borrowed(email),
+        };
+
+        assert_eq!(
+            handler().handle_command(&change(1, "two@example.com"), &mut repo),
+            Err(UserError::EmailTaken(Cow::Borrowed("two@example.com")))
+        );
+        assert_eq!(
+            handler().handle_command(&change(1, "invalid"), &mut repo),
+            Err(UserError::InvalidEmail(Cow::Borrowed("invalid")))
+        );
+        assert_eq!(
+            handler().handle_command(&change(3, "three@example.com"), &mut repo),
+            Err(UserError::NotFound(3))
+        );
+        assert_eq!(repo.get_user(1).unwrap().email, "one@example.com");
+
+        handler()
+            .handle_command(&change(1, "ONE@example.com"), &mut repo)
+            .unwrap();
+        assert_eq!(repo.get_user(1).unwrap().email, "ONE@example.com");
+    }
+
+    #[test]
+    fn test_delete_user() {
+        let mut repo = repo_with_users();
+
+        handler()
+            .handle_command(&DeleteUser { id: 1 }, &mut repo)
+            .unwrap();
+        assert!(repo.get_user(1).is_none());
+        assert!(repo.get_user(2).is_some());
+
+        assert_eq!(
+            handler().handle_command(&DeleteUser { id: 1 }, &mut repo),
+            Err(UserError::NotFound(1))
+        );
+    }
+}
@@ -0,0 +1,158 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use task_1_6::{
+    AsyncStorage, AsyncUserRepositoryDyn, AsyncUserRepositoryStatic, Storage, TryRepositoryError,
+    User, UserRepositoryDyn, UserRepositoryStatic,
+};
+
+use crate::async_handler::{AsyncUserRepository, RepositoryFuture};
+use crate::{UserError, UserRepository};
+
+// UserRepository implementations delegating to the task_1_6 repositories, so
+// command handlers can run against any of their storage backends.
+
+impl UserRepository for UserRepositoryDyn {
+    fn add_user(&mut self, user: User) -> Result<(), UserError> {
+        Ok(UserRepositoryDyn::add_user(self, user)?)
+    }
+
+    fn get_user(&self, id: u64) -> Option<Cow<'_, User>> {
+        UserRepositoryDyn::get_user(self, id)
+    }
+
+    fn update_user(&mut self, user: User) -> Result<(), UserError> {
+        Ok(UserRepositoryDyn::update_user(self, user)?)
+    }
+
+    fn remove_user(&mut self, id: u64) -> Option<User> {
+        UserRepositoryDyn::remove_user(self, id)
+    }
+
+    fn find_by_email(&self, email: &str) -> Option<Cow<'_, User>> {
+        UserRepositoryDyn::find_by_email(self, email)
+    }
+}
+
+impl<S> UserRepository for UserRepositoryStatic<S>
+where
+    S: Storage<u64, User>,
+{
+    fn add_user(&mut self, user: User) -> Result<(), UserError> {
+        Ok(UserRepositoryStatic::add_user(self, user)?)
+    }
+
+    fn get_user(&self, id: u64) -> Option<Cow<'_, User>> {
+        UserRepositoryStatic::get_user(self, id)
+    }
+
+    fn update_user(&mut self, user: User) -> Result<(), UserError> {
+        Ok(UserRepositoryStatic::update_user(self, user)?)
+    }
+
+    fn remove_user(&mut self, id: u64) -> Option<User> {
+        UserRepositoryStatic::remove_user(self, id)
+    }
+
+    fn find_by_email(&self, email: &str) -> Option<Cow<'_, User>> {
+        UserRepositoryStatic::find_by_email(self, email)
+    }
+}
+
+// AsyncUserRepository implementations delegating to the task_1_6 async
+// repositories, whose storage errors are reported by their messages.
+
+impl<E: fmt::Display> AsyncUserRepository for AsyncUserRepositoryDyn<E> {
+    fn add_user(&mut self, user: User) -> RepositoryFuture<'_, Result<(), UserError>> {
+        Box::pin(async move {
+            let res = AsyncUserRepositoryDyn::add_user(self, user).await;
+            res.map_err(write_error)
+        })
+    }
+
+    fn get_user(&self, id: u64) -> RepositoryFuture<'_, Result<Option<User>, UserError>> {
+        Box::pin(async move {
+            let res = AsyncUserRepositoryDyn::get_user(self, id).await;
+            res.map_err(storage_error)
+        })
+    }
+
+    fn update_user(&mut self, user: User) -> RepositoryFuture<'_, Result<(), UserError>> {
+        Box::pin(async move {
+            let res = AsyncUserRepositoryDyn::update_user(self, user).await;
+            res.map_err(write_error)
+        })
+    }
+
+    fn remove_user(&mut self, id: u64) -> RepositoryFuture<'_, Result<Option<User>, UserError>> {
+        Box::pin(async move {
+            let res = AsyncUserRepositoryDyn::remove_user(self, id).await;
+            res.map_err(storage_error)
+        })
+    }
+
+    fn find_by_email<'a>(
+        &'a self,
+        email: &'a str,
+    ) -> RepositoryFuture<'a, Result<Option<User>, UserError>> {
+        Box::pin(async move {
+            let res = AsyncUserRepositoryDyn::find_by_email(self, email).await;
+            res.map_err(storage_error)
+        })
+    }
+}
+
+impl<S> AsyncUserRepository for AsyncUserRepositoryStatic<S>
+where
+    S: AsyncStorage<u64, User>,
+    S::Error: fmt::Display,
+{
+    fn add_user(&mut self, user: User) -> RepositoryFuture<'_, Result<(), UserError>> {
+        Box::pin(async move {
+            let res = AsyncUserRepositoryStatic::add_user(self, user).await;
+            res.map_err(write_error)
+        })
+    }
+
+    fn get_user(&self, id: u64) -> RepositoryFuture<'_, Result<Option<User>, UserError>> {
+        Box::pin(async move {
+            let res = AsyncUserRepositoryStatic::get_user(self, id).await;
+            res.map_err(storage_error)
+        })
+    }
+
+    fn update_user(&mut self, user: User) -> RepositoryFuture<'_, Result<(), UserError>> {
+        Box::pin(async move {
+            let res = AsyncUserRepositoryStatic::update_user(self, user).await;
+            res.map_err(write_error)
+        })
+    }
+
+    fn remove_user(&mut self, id: u64) -> RepositoryFuture<'_, Result<Option<User>, UserError>> {
+        Box::pin(async move {
+            let res = AsyncUserRepositoryStatic::remove_user(self, id).await;
+            res.map_err(storage_error)
+        })
+    }
+
+    fn find_by_email<'a>(
+        &'a self,
+        email: &'a str,
+    ) -> RepositoryFuture<'a, Result<Option<User>, UserError>> {
+        Box::pin(async move {
+            let res = AsyncUserRepositoryStatic::find_by_email(self, email).await;
+            res.map_err(storage_error)
+        })
+    }
+}
+
+fn storage_error(e: impl fmt::Display) -> UserError {
+    UserError::Storage(e.to_string().into())
+}
+
+fn write_error<E: fmt::Display>(e: TryRepositoryError<E>) -> UserError {
+    match e {
+        TryRepositoryError::Storage(e) => storage_error(e),
+        TryRepositoryError::Repository(e) => e.into(),
+    }
+}
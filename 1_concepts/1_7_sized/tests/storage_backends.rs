@@ -0,0 +1,94 @@
+use std::borrow::Cow;
+
+use task_1_6::{
+    CachedStorage, FileStorage, HashMapStorage, SqliteStorage, User, UserRepositoryDyn,
+    UserRepositoryStatic,
+};
+use task_1_7::{
+    ActivateUser, ChangeEmail, CommandBus, CreateUser, DeleteUser, UserError, UserRepository,
+};
+
+fn bus() -> CommandBus<dyn UserRepository, (), UserError> {
+    let handler = User {
+        id: 0,
+        email: Cow::Borrowed("placeholder"),
+        activated: false,
+    };
+    let mut bus = CommandBus::new();
+    bus.register::<CreateUser, _>(handler.clone())
+        .register::<ActivateUser, _>(handler.clone())
+        .register::<ChangeEmail, _>(handler.clone())
+        .register::<DeleteUser, _>(handler);
+    bus
+}
+
+// Runs the same user lifecycle against the given repository.
+fn lifecycle(repo: &mut (dyn UserRepository + 'static)) {
+    let bus = bus();
+    let create = |id, email| CreateUser {
+        id,
+        email: Cow::Borrowed(email),
+    };
+
+    bus.dispatch(&create(1, "one@example.com"), repo).unwrap();
+    bus.dispatch(&create(2, "two@example.com"), repo).unwrap();
+    assert_eq!(
+        bus.dispatch(&create(1, "three@example.com"), repo),
+        Err(UserError::AlreadyExists(1))
+    );
+    assert_eq!(
+        bus.dispatch(&create(3, "TWO@example.com"), repo),
+        Err(UserError::EmailTaken(Cow::Borrowed("TWO@example.com")))
+    );
+
+    bus.dispatch(&ActivateUser { id: 1 }, repo).unwrap();
+    let change = ChangeEmail {
+        id: 1,
+        email: Cow::Borrowed("two@example.com"),
+    };
+    assert_eq!(
+        bus.dispatch(&change, repo),
+        Err(UserError::EmailTaken(Cow::Borrowed("two@example.com")))
+    );
+    assert_eq!(
+        repo.get_user(1).as_deref(),
+        Some(&User {
+            id: 1,
+            email: Cow::Borrowed("one@example.com"),
+            activated: true,
+        })
+    );
+
+    bus.dispatch(&DeleteUser { id: 2 }, repo).unwrap();
+    assert_eq!(repo.get_user(2), None);
+    assert_eq!(
+        bus.dispatch(&DeleteUser { id: 2 }, repo),
+        Err(UserError::NotFound(2))
+    );
+}
+
+#[test]
+fn test_dynamic_dispatch_with_hash_map_storage() {
+    lifecycle(&mut UserRepositoryDyn::new(Box::new(HashMapStorage::new())));
+}
+
+#[test]
+fn test_dynamic_dispatch_with_sqlite_storage() {
+    let storage = SqliteStorage::open_in_memory().unwrap();
+    lifecycle(&mut UserRepositoryDyn::new(Box::new(storage)));
+}
+
+#[test]
+fn test_static_dispatch_with_file_storage() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("users.log");
+
+    let storage = FileStorage::open(&path).unwrap();
+    lifecycle(&mut UserRepositoryStatic::new(CachedStorage::new(
+        storage, 8,
+    )));
+
+    let repo = UserRepositoryStatic::new(FileStorage::open(&path).unwrap());
+    assert!(repo.get_user(1).unwrap().activated);
+    assert_eq!(repo.get_user(2), None);
+}
@@ -0,0 +1,65 @@
+// Compile-time assertions of thread-safety guarantees
+//
+// Each macro accepts one or more comma-separated types and expands to a
+// `const _` item, so it can be used both at module level and inside function
+// bodies. A violated assertion fails the compilation.
+
+// Asserts the given types are `Send`.
+#[macro_export]
+macro_rules! assert_send {
+    ($($ty:ty),+ $(,)?) => {
+        const _: fn() = || {
+            fn assert_send<T: ?Sized + ::core::marker::Send>() {}
+            $(assert_send::<$ty>();)+
+        };
+    };
+}
+
+// Asserts the given types are `Sync`.
+#[macro_export]
+macro_rules! assert_sync {
+    ($($ty:ty),+ $(,)?) => {
+        const _: fn() = || {
+            fn assert_sync<T: ?Sized + ::core::marker::Sync>() {}
+            $(assert_sync::<$ty>();)+
+        };
+    };
+}
+
+// Asserts the given types are `!Send`.
+//
+// Negative bounds can't be expressed directly, so this relies on type
+// inference being ambiguous for `Send` types: they implement `AmbiguousIfSend`
+// twice, while `!Send` ones only once.
+#[macro_export]
+macro_rules! assert_not_send {
+    ($($ty:ty),+ $(,)?) => {
+        const _: fn() = || {
+            trait AmbiguousIfSend<A> {
+                fn some_item() {}
+            }
+            impl<T: ?Sized> AmbiguousIfSend<()> for T {}
+            struct Invalid;
+            impl<T: ?Sized + ::core::marker::Send> AmbiguousIfSend<Invalid> for T {}
+            $(let _ = <$ty as AmbiguousIfSend<_>>::some_item;)+
+        };
+    };
+}
+
+// Asserts the given types are `!Sync`.
+//
+// See `assert_not_send!` for how it works.
+#[macro_export]
+macro_rules! assert_not_sync {
+    ($($ty:ty),+ $(,)?) => {
+        const _: fn() = || {
+            trait AmbiguousIfSync<A> {
+                fn some_item() {}
+            }
+            impl<T: ?Sized> AmbiguousIfSync<()> for T {}
+            struct Invalid;
+            impl<T: ?Sized + ::core::marker::Sync> AmbiguousIfSync<Invalid> for T {}
+            $(let _ = <$ty as AmbiguousIfSync<_>>::some_item;)+
+        };
+    };
+}
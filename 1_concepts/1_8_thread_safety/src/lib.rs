@@ -0,0 +1,95 @@
+use std::cell::UnsafeCell;
+use std::marker::PhantomData;
+
+mod assertions;
+pub mod thread_bound;
+
+pub use self::thread_bound::{Disconnected, ThreadBound, ThreadBoundToken};
+
+// 1. OnlySync: Sync but not Send
+pub struct OnlySync {
+    _marker: PhantomData<*const ()>, // Raw pointer to prevent Send
+}
+
+unsafe impl Sync for OnlySync {} // Manually implementing Sync
+
+impl OnlySync {
+    pub fn new() -> Self {
+        Self {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl Default for OnlySync {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// 2. OnlySend: Send but not Sync
+pub struct OnlySend {
+    _marker: PhantomData<*const ()>, // Raw pointer to prevent Sync
+}
+
+unsafe impl Send for OnlySend {} // Manually implementing Send
+
+impl OnlySend {
+    pub fn new() -> Self {
+        Self {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl Default for OnlySend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// 3. SyncAndSend: Sync and Send
+pub struct SyncAndSend;
+
+unsafe impl Sync for SyncAndSend {}
+unsafe impl Send for SyncAndSend {}
+
+// 4. NotSyncNotSend: Neither Sync nor Send
+pub struct NotSyncNotSend {
+    data: UnsafeCell<i32>,
+    _not_send_or_sync: PhantomData<*const ()>, // Marker to prevent Send and Sync
+}
+
+impl NotSyncNotSend {
+    pub fn new(data: i32) -> Self {
+        Self {
+            data: UnsafeCell::new(data),
+            _not_send_or_sync: PhantomData,
+        }
+    }
+
+    pub fn get(&self) -> i32 {
+        // SAFETY: `Self` is `!Sync`, so no other thread can access `data`,
+        //         and no reference to it ever escapes.
+        unsafe { *self.data.get() }
+    }
+
+    pub fn set(&self, data: i32) {
+        // SAFETY: `Self` is `!Sync`, so no other thread can access `data`,
+        //         and no reference to it ever escapes.
+        unsafe { *self.data.get() = data }
+    }
+}
+
+assert_sync!(OnlySync);
+assert_not_send!(OnlySync);
+assert_send!(OnlySend);
+assert_not_sync!(OnlySend);
+assert_send!(SyncAndSend);
+assert_sync!(SyncAndSend);
+assert_not_send!(NotSyncNotSend);
+assert_not_sync!(NotSyncNotSend);
+assert_not_send!(ThreadBound<i32>);
+assert_not_sync!(ThreadBound<i32>);
+assert_send!(ThreadBoundToken<std::rc::Rc<i32>>);
+assert_sync!(ThreadBoundToken<std::rc::Rc<i32>>);
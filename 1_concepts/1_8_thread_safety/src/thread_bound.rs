@@ -0,0 +1,196 @@
+use std::marker::PhantomData;
+use std::sync::mpsc;
+use std::{error, fmt};
+
+// Closure posted to the thread owning the value
+type Job<T> = Box<dyn FnOnce(&mut T) + Send>;
+
+// Error returned by ThreadBoundToken once the owning ThreadBound is dropped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Disconnected;
+
+impl fmt::Display for Disconnected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("owning thread is gone")
+    }
+}
+
+impl error::Error for Disconnected {}
+
+// Value confined to the thread it was created on
+//
+// Just like NotSyncNotSend, it's neither Send nor Sync whatever `T` is, so
+// the value never leaves its thread. Other threads reach it through Send
+// tokens, posting closures that run on the owning thread once it processes
+// them via `run_pending` or the `run` event loop.
+pub struct ThreadBound<T> {
+    value: T,
+    sender: mpsc::Sender<Job<T>>,
+    receiver: mpsc::Receiver<Job<T>>,
+    _not_send_or_sync: PhantomData<*const ()>, // Marker to prevent Send and Sync
+}
+
+impl<T> ThreadBound<T> {
+    pub fn new(value: T) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Self {
+            value,
+            sender,
+            receiver,
+            _not_send_or_sync: PhantomData,
+        }
+    }
+
+    // Returns a token to post closures from other threads with.
+    pub fn token(&self) -> ThreadBoundToken<T> {
+        ThreadBoundToken {
+            sender: self.sender.clone(),
+        }
+    }
+
+    pub fn get(&self) -> &T {
+        &self.value
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+
+    // Runs all the closures posted so far without blocking, and returns how
+    // many of them were run.
+    pub fn run_pending(&mut self) -> usize {
+        let mut count = 0;
+        while let Ok(job) = self.receiver.try_recv() {
+            job(&mut self.value);
+            count += 1;
+        }
+        count
+    }
+
+    // Runs posted closures until all the tokens are dropped, and returns the
+    // value afterwards.
+    pub fn run(self) -> T {
+        let Self {
+            mut value,
+            sender,
+            receiver,
+            ..
+        } = self;
+        drop(sender);
+        for job in receiver {
+            job(&mut value);
+        }
+        value
+    }
+}
+
+// Send and Sync handle to a ThreadBound value, whatever `T` is
+pub struct ThreadBoundToken<T> {
+    sender: mpsc::Sender<Job<T>>,
+}
+
+impl<T> Clone for ThreadBoundToken<T> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<T: 'static> ThreadBoundToken<T> {
+    // Posts the closure to be run against the value on its owning thread.
+    pub fn post<F>(&self, f: F) -> Result<(), Disconnected>
+    where
+        F: FnOnce(&mut T) + Send + 'static,
+    {
+        self.sender.send(Box::new(f)).map_err(|_| Disconnected)
+    }
+
+    // Posts the closure and blocks until the owning thread runs it, returning
+    // its result.
+    //
+    // Calling it on the owning thread itself deadlocks, as nothing runs the
+    // closure meanwhile.
+    pub fn call<F, R>(&self, f: F) -> Result<R, Disconnected>
+    where
+        F: FnOnce(&mut T) -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (tx, rx) = mpsc::sync_channel(1);
+        self.post(move |value| {
+            let _ = tx.send(f(value));
+        })?;
+        rx.recv().map_err(|_| Disconnected)
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+    use std::thread;
+
+    use super::*;
+
+    type Cache = Rc<RefCell<HashMap<u64, u64>>>;
+
+    #[test]
+    fn test_pool_shares_rc_cache() {
+        let cache = Cache::default();
+        let bound = ThreadBound::new(Rc::clone(&cache));
+        let token = bound.token();
+
+        thread::scope(|s| {
+            for worker in 0..4 {
+                let token = token.clone();
+                s.spawn(move || {
+                    for n in 0..10 {
+                        let key = worker * 10 + n;
+                        let prev = token
+                            .call(move |cache| cache.borrow_mut().insert(key, key * key))
+                            .unwrap();
+                        assert_eq!(prev, None);
+                    }
+                });
+            }
+            drop(token);
+            let cache = bound.run();
+            assert_eq!(cache.borrow().len(), 40);
+        });
+
+        assert_eq!(cache.borrow()[&13], 169);
+        assert_eq!(Rc::strong_count(&cache), 1);
+    }
+
+    #[test]
+    fn test_run_pending() {
+        let mut bound = ThreadBound::new(Vec::new());
+        let token = bound.token();
+
+        thread::spawn(move || {
+            for i in 0..3 {
+                token.post(move |v: &mut Vec<i32>| v.push(i)).unwrap();
+            }
+        })
+        .join()
+        .unwrap();
+
+        assert!(bound.get().is_empty());
+        assert_eq!(bound.run_pending(), 3);
+        assert_eq!(bound.run_pending(), 0);
+        assert_eq!(bound.get(), &[0, 1, 2]);
+    }
+
+    #[test]
+    fn test_token_outliving_owner_is_disconnected() {
+        let bound = ThreadBound::new(0);
+        let token = bound.token();
+        token.post(|n| *n += 1).unwrap();
+        drop(bound);
+
+        assert_eq!(token.post(|n| *n += 1), Err(Disconnected));
+        assert_eq!(token.call(|n| *n), Err(Disconnected));
+    }
+}
@@ -0,0 +1,15 @@
+use task_1_8::{
+    assert_not_send, assert_not_sync, assert_send, assert_sync, NotSyncNotSend, OnlySend, OnlySync,
+    SyncAndSend,
+};
+
+assert_sync!(OnlySync, SyncAndSend, &'static OnlySync);
+assert_send!(OnlySend, SyncAndSend, &'static OnlySync, Box<OnlySend>);
+assert_not_send!(OnlySync, NotSyncNotSend, &'static OnlySend);
+assert_not_sync!(OnlySend, NotSyncNotSend, Box<OnlySend>);
+
+#[test]
+fn violated_assertions_fail_to_compile() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,5 @@
+use task_1_8::{assert_send, NotSyncNotSend};
+
+assert_send!(NotSyncNotSend);
+
+fn main() {}
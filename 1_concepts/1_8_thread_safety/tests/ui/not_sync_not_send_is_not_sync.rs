@@ -0,0 +1,5 @@
+use task_1_8::{assert_sync, NotSyncNotSend};
+
+assert_sync!(NotSyncNotSend);
+
+fn main() {}
@@ -0,0 +1,5 @@
+use task_1_8::{assert_sync, OnlySend};
+
+assert_sync!(OnlySend);
+
+fn main() {}
@@ -0,0 +1,5 @@
+use task_1_8::{assert_not_send, OnlySend};
+
+assert_not_send!(OnlySend);
+
+fn main() {}
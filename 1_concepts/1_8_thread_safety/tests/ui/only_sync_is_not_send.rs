@@ -0,0 +1,5 @@
+use task_1_8::{assert_send, OnlySync};
+
+assert_send!(OnlySync);
+
+fn main() {}
@@ -0,0 +1,5 @@
+use task_1_8::{assert_not_sync, OnlySync};
+
+assert_not_sync!(OnlySync);
+
+fn main() {}
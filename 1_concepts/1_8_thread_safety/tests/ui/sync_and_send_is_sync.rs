@@ -0,0 +1,5 @@
+use task_1_8::{assert_not_sync, SyncAndSend};
+
+assert_not_sync!(SyncAndSend);
+
+fn main() {}
@@ -0,0 +1,5 @@
+use task_1_8::{assert_send, ThreadBound};
+
+assert_send!(ThreadBound<i32>);
+
+fn main() {}
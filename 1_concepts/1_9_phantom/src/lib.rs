@@ -0,0 +1,99 @@
+use std::marker::PhantomData;
+
+use rand::seq::SliceRandom;
+use rand::{thread_rng, Rng};
+
+pub mod quantity;
+pub mod registry;
+
+pub use self::{
+    quantity::{
+        ConvertTo, Kilometers, Meters, MetersPerSecond, Milliseconds, One, Quantity, Seconds, Unit,
+        UnitDiv, UnitMul,
+    },
+    registry::{facts_of, FactProvider, FactRegistry, LoadError},
+};
+
+// Define the Fact<T> struct with PhantomData
+pub struct Fact<T: ?Sized> {
+    _marker: PhantomData<T>,
+}
+
+impl<T: ?Sized> Fact<T> {
+    pub fn new() -> Self {
+        Self {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: ?Sized> Default for Fact<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Facts about any type, looked up in a FactProvider
+impl<T: ?Sized + 'static> Fact<T> {
+    // Returns a random built-in fact about `T`, if there is any.
+    pub fn fact(&self) -> Option<&'static str> {
+        self.fact_with(&mut thread_rng())
+    }
+
+    // Same as `fact`, but picks the fact with the given `rng`, so seeded ones
+    // give deterministic results.
+    pub fn fact_with(&self, rng: &mut (impl Rng + ?Sized)) -> Option<&'static str> {
+        self.fact_from(FactRegistry::builtin(), rng)
+    }
+
+    // Returns a random fact about `T` known by the `provider`, picked with
+    // the given `rng`.
+    pub fn fact_from<'p>(
+        &self,
+        provider: &'p (impl FactProvider + ?Sized),
+        rng: &mut (impl Rng + ?Sized),
+    ) -> Option<&'p str> {
+        facts_of::<T>(provider).choose(rng).map(|fact| &**fact)
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn test_builtin_facts() {
+        let fact = Fact::<Result<i32, String>>::new().fact().unwrap();
+        assert!(fact.starts_with("Result"));
+        assert_eq!(Fact::<i32>::new().fact(), None);
+    }
+
+    #[test]
+    fn test_seeded_rng_is_deterministic() {
+        let fact: Fact<Vec<i32>> = Fact::new();
+        let picks = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..10)
+                .map(|_| fact.fact_with(&mut rng).unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(picks(42), picks(42));
+    }
+
+    #[test]
+    fn test_custom_provider() {
+        struct Point;
+
+        let mut registry = FactRegistry::new();
+        registry.register::<Point, _>(["Point is zero-sized."]);
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let fact = Fact::<Point>::new().fact_from(&registry, &mut rng);
+        assert_eq!(fact, Some("Point is zero-sized."));
+        assert_eq!(Fact::<Point>::new().fact_with(&mut rng), None);
+    }
+}
@@ -1,92 +1,42 @@
-use rand::seq::SliceRandom;
-use rand::thread_rng;
-use std::marker::PhantomData;
+use std::collections::HashMap;
 
-// Define the Fact<T> struct with PhantomData
-pub struct Fact<T> {
-    _marker: PhantomData<T>,
-}
-
-impl<T> Fact<T> {
-    pub fn new() -> Self {
-        Self {
-            _marker: PhantomData,
-        }
-    }
-}
-
-// Specialization for Vec<T>
-impl<T> Fact<Vec<T>> {
-    pub fn fact(&self) -> &'static str {
-        let facts = [
-            "Vec is heap-allocated.",
-            "Vec may re-allocate on growing.",
-            "Vec has an amortized O(1) push operation.",
-        ];
-        let mut rng = thread_rng();
-        facts.choose(&mut rng).unwrap_or(&"Unknown fact about Vec.")
-    }
-}
-
-// Specialization for String
-impl Fact<String> {
-    pub fn fact(&self) -> &'static str {
-        let facts = [
-            "String is UTF-8 encoded.",
-            "String is dynamically sized.",
-            "String is backed by a Vec<u8>.",
-        ];
-        let mut rng = thread_rng();
-        facts
-            .choose(&mut rng)
-            .unwrap_or(&"Unknown fact about String.")
-    }
-}
-
-// Specialization for Option<T>
-impl<T> Fact<Option<T>> {
-    pub fn fact(&self) -> &'static str {
-        let facts = [
-            "Option represents an optional value.",
-            "Option can be None or Some.",
-            "Option is commonly used to handle nullable values.",
-        ];
-        let mut rng = thread_rng();
-        facts
-            .choose(&mut rng)
-            .unwrap_or(&"Unknown fact about Option.")
-    }
-}
-
-// Specialization for Result<T, E>
-impl<T, E> Fact<Result<T, E>> {
-    pub fn fact(&self) -> &'static str {
-        let facts = [
-            "Result represents success or failure.",
-            "Result has Ok and Err variants.",
-            "Result is used for error handling.",
-        ];
-        let mut rng = thread_rng();
-        facts
-            .choose(&mut rng)
-            .unwrap_or(&"Unknown fact about Result.")
-    }
-}
+use task_1_9::{Fact, FactRegistry, Meters, MetersPerSecond, Milliseconds, Quantity, Seconds};
 
 fn main() {
     // Fact about Vec
     let vec_fact: Fact<Vec<i32>> = Fact::new();
-    println!("Fact about Vec: {}", vec_fact.fact());
+    println!("Fact about Vec: {}", vec_fact.fact().unwrap());
 
     // Fact about String
     let string_fact: Fact<String> = Fact::new();
-    println!("Fact about String: {}", string_fact.fact());
+    println!("Fact about String: {}", string_fact.fact().unwrap());
 
     // Fact about Option
     let option_fact: Fact<Option<i32>> = Fact::new();
-    println!("Fact about Option: {}", option_fact.fact());
+    println!("Fact about Option: {}", option_fact.fact().unwrap());
 
     // Fact about Result
     let result_fact: Fact<Result<i32, String>> = Fact::new();
-    println!("Fact about Result: {}", result_fact.fact());
+    println!("Fact about Result: {}", result_fact.fact().unwrap());
+
+    // Facts loaded from a file
+    let mut registry = FactRegistry::new();
+    registry
+        .load_toml(include_str!("../facts.toml"))
+        .expect("facts.toml should be valid");
+    let mut rng = rand::thread_rng();
+
+    let map_fact: Fact<HashMap<String, i32>> = Fact::new();
+    let fact = map_fact.fact_from(&registry, &mut rng).unwrap();
+    println!("Fact about HashMap: {fact}");
+
+    let box_fact: Fact<Box<dyn Fn()>> = Fact::new();
+    let fact = box_fact.fact_from(&registry, &mut rng).unwrap();
+    println!("Fact about Box: {fact}");
+
+    // Quantities with units
+    let distance = Quantity::<Meters>::new(42.0);
+    let elapsed = Quantity::<Milliseconds>::new(3500.0);
+    let speed: Quantity<MetersPerSecond> = distance / elapsed.convert::<Seconds>();
+    println!("Speed over {distance} in {elapsed}: {speed:.2}");
 }
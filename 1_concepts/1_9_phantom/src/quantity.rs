@@ -0,0 +1,294 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+// Unit of measure marker
+pub trait Unit {
+    // Suffix used when displaying quantities of this unit
+    const SYMBOL: &'static str;
+}
+
+// Unit being the product of `Self` and `Rhs`
+pub trait UnitMul<Rhs> {
+    type Output: Unit;
+}
+
+// Unit being the quotient of `Self` and `Rhs`
+pub trait UnitDiv<Rhs> {
+    type Output: Unit;
+}
+
+// Unit `Self` is convertible to, with the `RATIO.0 / RATIO.1` factor
+pub trait ConvertTo<To>: Unit {
+    const RATIO: (u16, u16);
+}
+
+macro_rules! units {
+    ($($(#[$attr:meta])* $name:ident => $symbol:literal),+ $(,)?) => {$(
+        $(#[$attr])*
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        pub struct $name;
+
+        impl Unit for $name {
+            const SYMBOL: &'static str = $symbol;
+        }
+    )+};
+}
+
+units! {
+    // Unit of dimensionless quantities, such as ratios of the same unit
+    One => "",
+    Meters => "m",
+    Kilometers => "km",
+    Seconds => "s",
+    Milliseconds => "ms",
+    MetersPerSecond => "m/s",
+}
+
+macro_rules! conversions {
+    ($($from:ident => $to:ident: $num:literal / $den:literal),+ $(,)?) => {$(
+        impl ConvertTo<$to> for $from {
+            const RATIO: (u16, u16) = ($num, $den);
+        }
+
+        impl ConvertTo<$from> for $to {
+            const RATIO: (u16, u16) = ($den, $num);
+        }
+    )+};
+}
+
+conversions! {
+    Kilometers => Meters: 1000 / 1,
+    Seconds => Milliseconds: 1000 / 1,
+}
+
+impl<U: Unit> UnitDiv<U> for U {
+    type Output = One;
+}
+
+impl UnitDiv<Seconds> for Meters {
+    type Output = MetersPerSecond;
+}
+
+impl UnitDiv<MetersPerSecond> for Meters {
+    type Output = Seconds;
+}
+
+impl UnitMul<Seconds> for MetersPerSecond {
+    type Output = Meters;
+}
+
+impl UnitMul<MetersPerSecond> for Seconds {
+    type Output = Meters;
+}
+
+// Numeric value of the `U` unit
+//
+// Arithmetic only compiles for compatible units: quantities are added and
+// subtracted only within the same unit, while multiplying and dividing them
+// produces the derived unit. Other units require an explicit `convert`.
+pub struct Quantity<U, N = f64> {
+    value: N,
+    _unit: PhantomData<U>,
+}
+
+impl<U, N> Quantity<U, N> {
+    pub const fn new(value: N) -> Self {
+        Self {
+            value,
+            _unit: PhantomData,
+        }
+    }
+
+    pub fn value(&self) -> &N {
+        &self.value
+    }
+
+    pub fn into_value(self) -> N {
+        self.value
+    }
+
+    // Converts the quantity to the `V` unit.
+    //
+    // Integer quantities are truncated when converted to a coarser unit.
+    pub fn convert<V>(self) -> Quantity<V, N>
+    where
+        U: ConvertTo<V>,
+        N: Mul<Output = N> + Div<Output = N> + From<u16>,
+    {
+        let (num, den) = <U as ConvertTo<V>>::RATIO;
+        Quantity::new(self.value * N::from(num) / N::from(den))
+    }
+}
+
+impl<U, N: Clone> Clone for Quantity<U, N> {
+    fn clone(&self) -> Self {
+        Self::new(self.value.clone())
+    }
+}
+
+impl<U, N: Copy> Copy for Quantity<U, N> {}
+
+impl<U, N: Default> Default for Quantity<U, N> {
+    fn default() -> Self {
+        Self::new(N::default())
+    }
+}
+
+impl<U: Unit, N: fmt::Debug> fmt::Debug for Quantity<U, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Quantity({:?} {})", self.value, U::SYMBOL)
+    }
+}
+
+impl<U: Unit, N: fmt::Display> fmt::Display for Quantity<U, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Delegating keeps the formatting options, such as precision.
+        fmt::Display::fmt(&self.value, f)?;
+        if !U::SYMBOL.is_empty() {
+            write!(f, " {}", U::SYMBOL)?;
+        }
+        Ok(())
+    }
+}
+
+impl<U, N: PartialEq> PartialEq for Quantity<U, N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<U, N: Eq> Eq for Quantity<U, N> {}
+
+impl<U, N: PartialOrd> PartialOrd for Quantity<U, N> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.value.partial_cmp(&other.value)
+    }
+}
+
+impl<U, N: Ord> Ord for Quantity<U, N> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.value.cmp(&other.value)
+    }
+}
+
+impl<U, N: Add<Output = N>> Add for Quantity<U, N> {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.value + rhs.value)
+    }
+}
+
+impl<U, N: Sub<Output = N>> Sub for Quantity<U, N> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.value - rhs.value)
+    }
+}
+
+impl<U, N: Neg<Output = N>> Neg for Quantity<U, N> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(-self.value)
+    }
+}
+
+impl<U, V, N> Mul<Quantity<V, N>> for Quantity<U, N>
+where
+    U: UnitMul<V>,
+    N: Mul<Output = N>,
+{
+    type Output = Quantity<U::Output, N>;
+
+    fn mul(self, rhs: Quantity<V, N>) -> Self::Output {
+        Quantity::new(self.value * rhs.value)
+    }
+}
+
+impl<U, V, N> Div<Quantity<V, N>> for Quantity<U, N>
+where
+    U: UnitDiv<V>,
+    N: Div<Output = N>,
+{
+    type Output = Quantity<U::Output, N>;
+
+    fn div(self, rhs: Quantity<V, N>) -> Self::Output {
+        Quantity::new(self.value / rhs.value)
+    }
+}
+
+// Scaling by a plain number keeps the unit.
+impl<U, N: Mul<Output = N>> Mul<N> for Quantity<U, N> {
+    type Output = Self;
+
+    fn mul(self, rhs: N) -> Self {
+        Self::new(self.value * rhs)
+    }
+}
+
+impl<U, N: Div<Output = N>> Div<N> for Quantity<U, N> {
+    type Output = Self;
+
+    fn div(self, rhs: N) -> Self {
+        Self::new(self.value / rhs)
+    }
+}
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_unit_arithmetic() {
+        let a = Quantity::<Meters>::new(1.5);
+        let b = Quantity::<Meters>::new(2.0);
+
+        assert_eq!(a + b, Quantity::new(3.5));
+        assert_eq!(b - a, Quantity::new(0.5));
+        assert_eq!(-a * 2.0, Quantity::new(-3.0));
+        assert!(a < b);
+    }
+
+    #[test]
+    fn test_derived_units() {
+        let distance = Quantity::<Meters>::new(100.0);
+        let time = Quantity::<Seconds>::new(8.0);
+
+        let speed: Quantity<MetersPerSecond> = distance / time;
+        assert_eq!(speed, Quantity::new(12.5));
+        let back: Quantity<Meters> = speed * time;
+        assert_eq!(back, distance);
+        let back: Quantity<Seconds> = distance / speed;
+        assert_eq!(back, time);
+        let ratio: Quantity<One> = distance / Quantity::<Meters>::new(50.0);
+        assert_eq!(ratio.into_value(), 2.0);
+    }
+
+    #[test]
+    fn test_conversions() {
+        let km = Quantity::<Kilometers, u32>::new(3);
+        assert_eq!(km.convert::<Meters>(), Quantity::new(3000));
+
+        let ms = Quantity::<Milliseconds, u64>::new(1500);
+        assert_eq!(ms.convert::<Seconds>(), Quantity::new(1));
+        let ms = Quantity::<Milliseconds>::new(1500.0);
+        assert_eq!(ms.convert::<Seconds>(), Quantity::new(1.5));
+        assert_eq!(ms.convert::<Seconds>().convert::<Milliseconds>(), ms);
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Quantity::<Meters, u32>::new(5).to_string(), "5 m");
+        assert_eq!(
+            format!("{:.2}", Quantity::<MetersPerSecond>::new(1.0 / 3.0)),
+            "0.33 m/s"
+        );
+        assert_eq!(Quantity::<Milliseconds, u8>::new(7).to_string(), "7 ms");
+        assert_eq!(Quantity::<One>::new(0.5).to_string(), "0.5");
+    }
+}
@@ -0,0 +1,370 @@
+use std::any::{self, TypeId};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::OnceLock;
+use std::{error, fmt, fs, io};
+
+// Source of facts about types
+pub trait FactProvider {
+    // Returns all the facts known about the type with the given `id` and
+    // `name`, which is the one returned by `std::any::type_name`.
+    fn facts(&self, id: TypeId, name: &str) -> &[Cow<'static, str>];
+}
+
+// Facts keyed either by exact types or by type names
+//
+// Named facts apply to every type sharing the name regardless of its generic
+// parameters, so facts registered for `Vec` cover both `Vec<i32>` and
+// `Vec<String>`. Names may be either bare ones (`Vec`) or full paths, the
+// latter taking precedence. The paths `std::any::type_name` reports aren't
+// stable across compiler versions, so they're registered from a type with
+// `register_generic` rather than spelled out. A bare name is ignored for a
+// type whose full path isn't registered while another one ending with the
+// same name is, so `my_crate::Vec` never gets the facts of the standard `Vec`.
+//
+// Only types named by plain paths are looked up by name, so tuples,
+// references, pointers, arrays, slices, trait objects and function pointers
+// get the facts registered for their exact types only.
+#[derive(Debug, Default)]
+pub struct FactRegistry {
+    by_type: HashMap<TypeId, Vec<Cow<'static, str>>>,
+    by_name: HashMap<Cow<'static, str>, Vec<Cow<'static, str>>>,
+}
+
+impl FactRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Returns the registry populated with the built-in facts about standard
+    // library types.
+    pub fn builtin() -> &'static Self {
+        static BUILTIN: OnceLock<FactRegistry> = OnceLock::new();
+        BUILTIN.get_or_init(|| {
+            let mut registry = Self::new();
+            for (type_name, facts) in BUILTIN_FACTS {
+                let path = path_of(type_name());
+                registry.register_name(path, facts.iter().copied().map(Cow::Borrowed));
+            }
+            registry
+        })
+    }
+
+    // Registers facts about exactly the type `T`.
+    pub fn register<T, I>(&mut self, facts: I) -> &mut Self
+    where
+        T: ?Sized + 'static,
+        I: IntoIterator,
+        I::Item: Into<Cow<'static, str>>,
+    {
+        let entry = self.by_type.entry(TypeId::of::<T>()).or_default();
+        entry.extend(facts.into_iter().map(Into::into));
+        self
+    }
+
+    // Registers facts about every instantiation of the generic type `T` is
+    // one of, so registering them for `Vec<()>` covers all the `Vec`s.
+    pub fn register_generic<T, I>(&mut self, facts: I) -> &mut Self
+    where
+        T: ?Sized + 'static,
+        I: IntoIterator,
+        I::Item: Into<Cow<'static, str>>,
+    {
+        self.register_name(path_of(any::type_name::<T>()), facts)
+    }
+
+    // Registers facts about every type named `name`.
+    pub fn register_name<I>(&mut self, name: impl Into<Cow<'static, str>>, facts: I) -> &mut Self
+    where
+        I: IntoIterator,
+        I::Item: Into<Cow<'static, str>>,
+    {
+        let entry = self.by_name.entry(name.into()).or_default();
+        entry.extend(facts.into_iter().map(Into::into));
+        self
+    }
+
+    // Registers named facts from a JSON object mapping type names to arrays
+    // of facts.
+    pub fn load_json(&mut self, src: &str) -> Result<&mut Self, serde_json::Error> {
+        let facts: HashMap<String, Vec<String>> = serde_json::from_str(src)?;
+        Ok(self.register_all(facts))
+    }
+
+    // Registers named facts from a TOML table mapping type names to arrays of
+    // facts.
+    pub fn load_toml(&mut self, src: &str) -> Result<&mut Self, toml::de::Error> {
+        let facts: HashMap<String, Vec<String>> = toml::from_str(src)?;
+        Ok(self.register_all(facts))
+    }
+
+    // Registers named facts from a `.json` or `.toml` file.
+    pub fn load_file(&mut self, path: impl AsRef<Path>) -> Result<&mut Self, LoadError> {
+        let path = path.as_ref();
+        let src = fs::read_to_string(path)?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Ok(self.load_json(&src)?),
+            Some("toml") => Ok(self.load_toml(&src)?),
+            _ => Err(LoadError::UnsupportedFormat(path.display().to_string())),
+        }
+    }
+
+    fn register_all(&mut self, facts: HashMap<String, Vec<String>>) -> &mut Self {
+        for (name, facts) in facts {
+            self.register_name(name, facts);
+        }
+        self
+    }
+}
+
+impl FactProvider for FactRegistry {
+    fn facts(&self, id: TypeId, name: &str) -> &[Cow<'static, str>] {
+        if let Some(facts) = self.by_type.get(&id) {
+            return facts;
+        }
+        let path = path_of(name);
+        if !is_plain_path(path) {
+            return &[];
+        }
+        if let Some(facts) = self.by_name.get(path) {
+            return facts;
+        }
+        let bare = path.rsplit("::").next().unwrap_or(path);
+        let ambiguous = self.by_name.keys().any(|name| {
+            name.rsplit_once("::")
+                .is_some_and(|(_, other)| other == bare)
+        });
+        match self.by_name.get(bare) {
+            Some(facts) if !ambiguous => facts,
+            _ => &[],
+        }
+    }
+}
+
+// Strips the generic parameters off the type `name`.
+fn path_of(name: &str) -> &str {
+    name.split('<').next().unwrap_or(name)
+}
+
+// Checks whether the `name` is a path like `alloc::vec::Vec`, rather than
+// a tuple, reference, pointer, array, slice, trait object or function pointer.
+fn is_plain_path(name: &str) -> bool {
+    !name.is_empty()
+        && name.split("::").all(|segment| {
+            segment.starts_with(|c: char| c.is_alphabetic() || c == '_')
+                && segment.chars().all(|c| c.is_alphanumeric() || c == '_')
+        })
+}
+
+// Returns all the facts `provider` knows about the type `T`.
+pub fn facts_of<T: ?Sized + 'static>(
+    provider: &(impl FactProvider + ?Sized),
+) -> &[Cow<'static, str>] {
+    provider.facts(TypeId::of::<T>(), any::type_name::<T>())
+}
+
+// Error of loading facts from a file
+#[derive(Debug)]
+pub enum LoadError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Toml(toml::de::Error),
+    UnsupportedFormat(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "failed to read facts: {e}"),
+            Self::Json(e) => write!(f, "invalid JSON facts: {e}"),
+            Self::Toml(e) => write!(f, "invalid TOML facts: {e}"),
+            Self::UnsupportedFormat(path) => write!(f, "unsupported facts file: {path}"),
+        }
+    }
+}
+
+impl error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Json(e) => Some(e),
+            Self::Toml(e) => Some(e),
+            Self::UnsupportedFormat(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for LoadError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for LoadError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Json(e)
+    }
+}
+
+impl From<toml::de::Error> for LoadError {
+    fn from(e: toml::de::Error) -> Self {
+        Self::Toml(e)
+    }
+}
+
+// `std::any::type_name` of a representative instantiation of a type
+type TypeName = fn() -> &'static str;
+
+// Built-in facts by the type names of their types, resolved at runtime as the
+// paths of the types aren't stable.
+const BUILTIN_FACTS: &[(TypeName, &[&str])] = &[
+    (
+        any::type_name::<Vec<()>>,
+        &[
+            "Vec is heap-allocated.",
+            "Vec may re-allocate on growing.",
+            "Vec has an amortized O(1) push operation.",
+        ],
+    ),
+    (
+        any::type_name::<String>,
+        &[
+            "String is UTF-8 encoded.",
+            "String is dynamically sized.",
+            "String is backed by a Vec<u8>.",
+        ],
+    ),
+    (
+        any::type_name::<Option<()>>,
+        &[
+            "Option represents an optional value.",
+            "Option can be None or Some.",
+            "Option is commonly used to handle nullable values.",
+        ],
+    ),
+    (
+        any::type_name::<Result<(), ()>>,
+        &[
+            "Result represents success or failure.",
+            "Result has Ok and Err variants.",
+            "Result is used for error handling.",
+        ],
+    ),
+];
+
+// Tests
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    struct Config;
+
+    #[test]
+    fn test_lookup_by_name_ignores_generics() {
+        let registry = FactRegistry::builtin();
+
+        assert_eq!(
+            facts_of::<Vec<i32>>(registry),
+            facts_of::<Vec<String>>(registry)
+        );
+        assert_eq!(facts_of::<Option<u8>>(registry).len(), 3);
+        assert!(facts_of::<HashMap<u8, u8>>(registry).is_empty());
+    }
+
+    #[test]
+    fn test_exact_type_takes_precedence() {
+        let mut registry = FactRegistry::new();
+        registry
+            .register_name("Vec", ["Vec is a vector."])
+            .register_generic::<Vec<()>, _>(["Vec lives in alloc."])
+            .register::<Vec<u8>, _>(["Vec<u8> is a byte buffer."]);
+
+        assert_eq!(
+            facts_of::<Vec<u8>>(&registry),
+            ["Vec<u8> is a byte buffer."]
+        );
+        assert_eq!(facts_of::<Vec<i8>>(&registry), ["Vec lives in alloc."]);
+    }
+
+    mod shadow {
+        pub struct Vec;
+        pub struct Box<T>(pub T);
+    }
+
+    #[test]
+    fn test_compound_types_are_not_looked_up_by_name() {
+        let registry = FactRegistry::builtin();
+
+        assert!(facts_of::<(i32, Vec<u8>)>(registry).is_empty());
+        assert!(facts_of::<(Vec<u8>,)>(registry).is_empty());
+        assert!(facts_of::<&Vec<u8>>(registry).is_empty());
+        assert!(facts_of::<&mut Option<u8>>(registry).is_empty());
+        assert!(facts_of::<*const String>(registry).is_empty());
+        assert!(facts_of::<[Vec<u8>; 3]>(registry).is_empty());
+        assert!(facts_of::<[String]>(registry).is_empty());
+        assert!(facts_of::<fn(Vec<u8>) -> Vec<u8>>(registry).is_empty());
+        assert!(facts_of::<dyn Fn() -> Option<u8>>(registry).is_empty());
+    }
+
+    #[test]
+    fn test_same_named_types_are_not_confused() {
+        let registry = FactRegistry::builtin();
+        assert!(facts_of::<shadow::Vec>(registry).is_empty());
+        assert_eq!(facts_of::<Vec<u8>>(registry).len(), 3);
+
+        let mut registry = FactRegistry::new();
+        registry
+            .register_name("Box", ["Some Box."])
+            .register_generic::<Box<()>, _>(["Box owns a heap allocation."]);
+        assert!(facts_of::<shadow::Box<u8>>(&registry).is_empty());
+        assert_eq!(
+            facts_of::<Box<u8>>(&registry),
+            ["Box owns a heap allocation."]
+        );
+
+        let mut registry = FactRegistry::new();
+        registry.register_name("Box", ["Some Box."]);
+        assert_eq!(facts_of::<shadow::Box<u8>>(&registry), ["Some Box."]);
+        assert_eq!(facts_of::<Box<u8>>(&registry), ["Some Box."]);
+    }
+
+    #[test]
+    fn test_load_json_and_toml() {
+        let mut registry = FactRegistry::new();
+        registry
+            .load_json(r#"{"HashMap": ["HashMap uses SipHash by default."]}"#)
+            .unwrap()
+            .load_toml("Config = [\"Config is ours.\", \"Config is zero-sized.\"]")
+            .unwrap();
+
+        assert_eq!(facts_of::<HashMap<String, u8>>(&registry).len(), 1);
+        assert_eq!(facts_of::<Config>(&registry).len(), 2);
+        assert!(registry.load_json("[1, 2]").is_err());
+        assert!(registry.load_toml("Config = 1").is_err());
+    }
+
+    #[test]
+    fn test_load_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let toml = dir.path().join("facts.toml");
+        fs::write(&toml, "Box = [\"Box owns a heap allocation.\"]").unwrap();
+        let yaml = dir.path().join("facts.yaml");
+        fs::write(&yaml, "Box: []").unwrap();
+
+        let mut registry = FactRegistry::new();
+        registry.load_file(&toml).unwrap();
+
+        assert_eq!(facts_of::<Box<dyn Fn()>>(&registry).len(), 1);
+        assert!(matches!(
+            registry.load_file(&yaml),
+            Err(LoadError::UnsupportedFormat(_))
+        ));
+        assert!(matches!(
+            registry.load_file(dir.path().join("missing.json")),
+            Err(LoadError::Io(_))
+        ));
+    }
+}
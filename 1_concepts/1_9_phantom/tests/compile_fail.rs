@@ -0,0 +1,5 @@
+#[test]
+fn incompatible_units_fail_to_compile() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,5 @@
+use task_1_9::{Milliseconds, Quantity, Seconds};
+
+fn main() {
+    let _ = Quantity::<Seconds>::new(1.0) + Quantity::<Milliseconds>::new(500.0);
+}
@@ -0,0 +1,5 @@
+use task_1_9::{Meters, Quantity, Seconds};
+
+fn main() {
+    let _ = Quantity::<Meters>::new(1.0).convert::<Seconds>();
+}
@@ -0,0 +1,5 @@
+use task_1_9::{Meters, Quantity};
+
+fn main() {
+    let _ = Quantity::<Meters>::new(2.0) * Quantity::<Meters>::new(3.0);
+}
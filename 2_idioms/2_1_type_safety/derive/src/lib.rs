@@ -0,0 +1,370 @@
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{
+    parenthesized, parse_macro_input, Data, DeriveInput, Error, Fields, GenericParam, Ident,
+    LitStr, Token, Type,
+};
+
+/// Generates the typestate pattern for a struct generic over its state.
+///
+/// ```ignore
+/// #[derive(Typestate)]
+/// #[typestate(
+///     erased = AnyOrder,
+///     states(Placed, Paid = "paid", Shipped),
+///     transitions(
+///         pay: Placed -> Paid,
+///         ship: Paid -> Shipped,
+///         Placed -> Shipped,
+///     ),
+/// )]
+/// pub struct Order<S> {
+///     id: u64,
+///     state: PhantomData<S>,
+/// }
+/// ```
+///
+/// The struct must have named fields and a single type parameter, used only
+/// in its `PhantomData` field. The derive generates:
+/// - a `state` module (renamed with `module = name`) containing:
+///   - a zero-sized marker type per state;
+///   - a sealed `State` trait, exposing the state's stable `TAG`, which is
+///     the snake-cased state name unless given explicitly;
+///   - a `TransitionTo<T>` trait, implemented for each allowed transition;
+/// - a private `transition()` method, moving the fields into the struct in
+///   any state the current one is allowed to transition to, and a private
+///   unchecked `retype()` one, moving them into the struct in any state;
+/// - a method for each named transition, with the struct's visibility;
+/// - the `erased` enum having a variant per state, with a `tag()` method,
+///   conversions from and into the struct in each state, and a private
+///   `retag()` method wrapping the struct in the variant with the given tag.
+#[proc_macro_derive(Typestate, attributes(typestate))]
+pub fn derive_typestate(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// Arguments of the `#[typestate(...)]` attribute
+struct Args {
+    module: Ident,
+    erased: Ident,
+    states: Vec<StateDef>,
+    transitions: Vec<TransitionDef>,
+}
+
+/// State declared as either `Name` or `Name = "tag"`
+struct StateDef {
+    name: Ident,
+    tag: LitStr,
+}
+
+/// Transition declared as either `from -> to` or `method: from -> to`
+struct TransitionDef {
+    method: Option<Ident>,
+    from: Ident,
+    to: Ident,
+}
+
+impl Parse for Args {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut module = None;
+        let mut erased = None;
+        let mut states = None;
+        let mut transitions = None;
+        while !input.is_empty() {
+            let key: Ident = input.parse()?;
+            match key.to_string().as_str() {
+                "module" => {
+                    input.parse::<Token![=]>()?;
+                    module = Some(input.parse()?);
+                }
+                "erased" => {
+                    input.parse::<Token![=]>()?;
+                    erased = Some(input.parse()?);
+                }
+                "states" => {
+                    let content;
+                    parenthesized!(content in input);
+                    let defs = Punctuated::<StateDef, Token![,]>::parse_terminated(&content)?;
+                    states = Some(defs.into_iter().collect());
+                }
+                "transitions" => {
+                    let content;
+                    parenthesized!(content in input);
+                    let defs = Punctuated::<TransitionDef, Token![,]>::parse_terminated(&content)?;
+                    transitions = Some(defs.into_iter().collect());
+                }
+                _ => return Err(Error::new(key.span(), "unknown typestate argument")),
+            }
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        Ok(Self {
+            module: module.unwrap_or_else(|| Ident::new("state", Span::call_site())),
+            erased: erased.ok_or_else(|| input.error("missing `erased = Name` argument"))?,
+            states: states.ok_or_else(|| input.error("missing `states(...)` argument"))?,
+            transitions: transitions.unwrap_or_default(),
+        })
+    }
+}
+
+impl Parse for StateDef {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name: Ident = input.parse()?;
+        let tag = if input.parse::<Option<Token![=]>>()?.is_some() {
+            input.parse()?
+        } else {
+            LitStr::new(&snake_case(&name.to_string()), name.span())
+        };
+        Ok(Self { name, tag })
+    }
+}
+
+impl Parse for TransitionDef {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut method = None;
+        if input.peek2(Token![:]) {
+            method = Some(input.parse()?);
+            input.parse::<Token![:]>()?;
+        }
+        let from = input.parse()?;
+        input.parse::<Token![->]>()?;
+        let to = input.parse()?;
+        Ok(Self { method, from, to })
+    }
+}
+
+fn snake_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    for (i, c) in name.char_indices() {
+        if c.is_uppercase() && i > 0 {
+            out.push('_');
+        }
+        out.extend(c.to_lowercase());
+    }
+    out
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let attr = input
+        .attrs
+        .iter()
+        .find(|attr| attr.path().is_ident("typestate"))
+        .ok_or_else(|| Error::new_spanned(&input.ident, "missing `#[typestate(...)]` attribute"))?;
+    let Args {
+        module,
+        erased,
+        states,
+        transitions,
+    } = attr.parse_args()?;
+
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "typestate must be a struct",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(Error::new_spanned(
+            &input.ident,
+            "typestate must have named fields",
+        ));
+    };
+    let mut params = input.generics.params.iter();
+    let (Some(GenericParam::Type(_)), None) = (params.next(), params.next()) else {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "typestate must have a single type parameter for its state",
+        ));
+    };
+
+    for (i, state) in states.iter().enumerate() {
+        if let Some(dup) = states[..i]
+            .iter()
+            .find(|s| s.tag.value() == state.tag.value())
+        {
+            let msg = format!(
+                "tag {:?} is already used by `{}`",
+                state.tag.value(),
+                dup.name
+            );
+            return Err(Error::new(state.tag.span(), msg));
+        }
+    }
+    for t in &transitions {
+        for name in [&t.from, &t.to] {
+            if !states.iter().any(|s| s.name == *name) {
+                return Err(Error::new(name.span(), format!("unknown state `{name}`")));
+            }
+        }
+    }
+
+    let vis = &input.vis;
+    let ty = &input.ident;
+    let names: Vec<_> = states.iter().map(|s| &s.name).collect();
+    let tags: Vec<_> = states.iter().map(|s| &s.tag).collect();
+
+    // Moves all the fields, resetting the `PhantomData` one.
+    let moved = fields.named.iter().map(|field| {
+        let name = &field.ident;
+        if is_phantom_data(&field.ty) {
+            quote!(#name: ::core::marker::PhantomData)
+        } else {
+            quote!(#name: self.#name)
+        }
+    });
+
+    let allowed = transitions
+        .iter()
+        .map(|TransitionDef { from, to, .. }| quote!(impl TransitionTo<#to> for #from {}));
+    let methods = transitions.iter().filter_map(|t| {
+        let TransitionDef { from, to, .. } = t;
+        let method = t.method.as_ref()?;
+        let doc = format!("Transition from `{from}` to `{to}`");
+        Some(quote! {
+            impl #ty<#module::#from> {
+                #[doc = #doc]
+                #vis fn #method(self) -> #ty<#module::#to> {
+                    self.transition()
+                }
+            }
+        })
+    });
+
+    let state_doc = format!("States of [`{ty}`](super::{ty})");
+    let erased_doc = format!("[`{ty}`] in a state known only at runtime");
+
+    Ok(quote! {
+        #[doc = #state_doc]
+        #vis mod #module {
+            mod sealed {
+                pub trait Sealed {}
+            }
+
+            /// State of the typestate
+            pub trait State: sealed::Sealed + ::core::marker::Sized + 'static {
+                /// Tag identifying the state, which must never change
+                const TAG: &'static str;
+
+                /// Wraps the `value` into the erased enum.
+                fn into_erased(value: super::#ty<Self>) -> super::#erased;
+
+                /// Unwraps the `value` if it's in this state, or gives it back
+                /// otherwise.
+                fn from_erased(
+                    value: super::#erased,
+                ) -> ::core::result::Result<super::#ty<Self>, super::#erased>;
+            }
+
+            /// Marks `Self` as allowed to transition to `T`
+            pub trait TransitionTo<T: State>: State {}
+
+            /// Tags of all the states, in the declaration order
+            pub const TAGS: &[&str] = &[#(#tags),*];
+
+            #(
+                pub struct #names;
+
+                impl sealed::Sealed for #names {}
+
+                impl State for #names {
+                    const TAG: &'static str = #tags;
+
+                    fn into_erased(value: super::#ty<Self>) -> super::#erased {
+                        super::#erased::#names(value)
+                    }
+
+                    fn from_erased(
+                        value: super::#erased,
+                    ) -> ::core::result::Result<super::#ty<Self>, super::#erased> {
+                        match value {
+                            super::#erased::#names(value) => ::core::result::Result::Ok(value),
+                            #[allow(unreachable_patterns)]
+                            value => ::core::result::Result::Err(value),
+                        }
+                    }
+                }
+            )*
+
+            #(#allowed)*
+        }
+
+        impl<__S: #module::State> #ty<__S> {
+            /// Moves the fields into the struct in the `__T` state, whether
+            /// the transition is allowed or not.
+            #[allow(dead_code)]
+            fn retype<__T: #module::State>(self) -> #ty<__T> {
+                #ty { #(#moved),* }
+            }
+
+            /// Moves the fields into the struct in the `__T` state.
+            #[allow(dead_code)]
+            fn transition<__T: #module::State>(self) -> #ty<__T>
+            where
+                __S: #module::TransitionTo<__T>,
+            {
+                self.retype()
+            }
+        }
+
+        #(#methods)*
+
+        #[doc = #erased_doc]
+        #vis enum #erased {
+            #(#names(#ty<#module::#names>)),*
+        }
+
+        impl #erased {
+            /// Returns the tag of the current state.
+            #vis fn tag(&self) -> &'static str {
+                match self {
+                    #(Self::#names(_) => <#module::#names as #module::State>::TAG),*
+                }
+            }
+
+            /// Wraps the `value` into the variant with the given `tag`,
+            /// whether its current state is allowed to transition there or
+            /// not, or gives it back if the tag is unknown.
+            #[allow(dead_code)]
+            fn retag<__S: #module::State>(
+                value: #ty<__S>,
+                tag: &str,
+            ) -> ::core::result::Result<Self, #ty<__S>> {
+                match tag {
+                    #(#tags => ::core::result::Result::Ok(Self::#names(value.retype())),)*
+                    _ => ::core::result::Result::Err(value),
+                }
+            }
+        }
+
+        impl<__S: #module::State> ::core::convert::From<#ty<__S>> for #erased {
+            fn from(value: #ty<__S>) -> Self {
+                <__S as #module::State>::into_erased(value)
+            }
+        }
+
+        impl<__S: #module::State> ::core::convert::TryFrom<#erased> for #ty<__S> {
+            type Error = #erased;
+
+            fn try_from(value: #erased) -> ::core::result::Result<Self, #erased> {
+                <__S as #module::State>::from_erased(value)
+            }
+        }
+    })
+}
+
+fn is_phantom_data(ty: &Type) -> bool {
+    let Type::Path(path) = ty else {
+        return false;
+    };
+    path.path
+        .segments
+        .last()
+        .is_some_and(|segment| segment.ident == "PhantomData")
+}
@@ -0,0 +1,102 @@
+use std::marker::PhantomData;
+
+use task_2_1_derive::Typestate;
+
+#[derive(Typestate)]
+#[typestate(
+    erased = AnyOrder,
+    states(Placed, Paid, Shipped, Cancelled = "canceled"),
+    transitions(
+        pay: Placed -> Paid,
+        ship: Paid -> Shipped,
+        cancel: Placed -> Cancelled,
+        Paid -> Cancelled,
+    ),
+)]
+pub struct Order<S> {
+    id: u64,
+    items: Vec<&'static str>,
+    _state: PhantomData<S>,
+}
+
+impl Order<state::Placed> {
+    pub fn new(id: u64, items: Vec<&'static str>) -> Self {
+        Self {
+            id,
+            items,
+            _state: PhantomData,
+        }
+    }
+}
+
+impl Order<state::Paid> {
+    // Custom transition, reusing the generated allowed ones
+    pub fn refund(self) -> Order<state::Cancelled> {
+        self.transition()
+    }
+}
+
+#[derive(Typestate)]
+#[typestate(
+    module = invoice_state,
+    erased = AnyInvoice,
+    states(Draft, Issued, Settled),
+    transitions(issue: Draft -> Issued, settle: Issued -> Settled),
+)]
+struct Invoice<State> {
+    amount: u64,
+    state: PhantomData<State>,
+}
+
+#[test]
+fn test_named_transitions_move_fields() {
+    let order = Order::new(1, vec!["book", "pen"]).pay().ship();
+    assert_eq!(order.id, 1);
+    assert_eq!(order.items, ["book", "pen"]);
+
+    let order = Order::new(2, vec![]).pay().refund();
+    assert_eq!(order.id, 2);
+}
+
+#[test]
+fn test_tags() {
+    use state::State as _;
+
+    assert_eq!(state::Placed::TAG, "placed");
+    assert_eq!(state::Cancelled::TAG, "canceled");
+    assert_eq!(state::TAGS, ["placed", "paid", "shipped", "canceled"]);
+    assert_eq!(
+        AnyOrder::from(Order::new(1, vec![]).cancel()).tag(),
+        "canceled"
+    );
+}
+
+#[test]
+fn test_erased_round_trip() {
+    let order = AnyOrder::from(Order::new(1, vec!["book"]).pay());
+    let order = Order::<state::Shipped>::try_from(order).err().unwrap();
+    let AnyOrder::Paid(order) = order else {
+        panic!("order should be paid");
+    };
+    assert_eq!(order.ship().items, ["book"]);
+}
+
+#[test]
+fn test_retag() {
+    let order = AnyOrder::retag(Order::new(3, vec![]), "shipped")
+        .ok()
+        .unwrap();
+    assert!(matches!(order, AnyOrder::Shipped(_)));
+    assert!(AnyOrder::retag(Order::new(3, vec![]), "lost").is_err());
+}
+
+#[test]
+fn test_custom_module_name() {
+    let invoice = Invoice::<invoice_state::Draft> {
+        amount: 100,
+        state: PhantomData,
+    };
+    let invoice = invoice.issue().settle();
+    assert_eq!(invoice.amount, 100);
+    assert_eq!(AnyInvoice::from(invoice).tag(), "settled");
+}
@@ -0,0 +1,11 @@
+use std::marker::PhantomData;
+
+use task_2_1_derive::Typestate;
+
+#[derive(Typestate)]
+#[typestate(erased = AnyOrder, states(Placed, Paid = "placed"))]
+struct Order<S> {
+    state: PhantomData<S>,
+}
+
+fn main() {}
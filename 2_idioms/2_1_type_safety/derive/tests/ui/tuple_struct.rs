@@ -0,0 +1,9 @@
+use std::marker::PhantomData;
+
+use task_2_1_derive::Typestate;
+
+#[derive(Typestate)]
+#[typestate(erased = AnyOrder, states(Placed, Paid))]
+struct Order<S>(PhantomData<S>);
+
+fn main() {}
@@ -0,0 +1,17 @@
+use std::marker::PhantomData;
+
+use task_2_1_derive::Typestate;
+
+#[derive(Typestate)]
+#[typestate(erased = AnyOrder, states(Placed, Paid), transitions(pay: Placed -> Paid))]
+struct Order<S> {
+    state: PhantomData<S>,
+}
+
+impl Order<state::Paid> {
+    fn unpay(self) -> Order<state::Placed> {
+        self.transition()
+    }
+}
+
+fn main() {}
@@ -0,0 +1,164 @@
+use std::borrow::Cow;
+use std::{error, fmt};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{state, AnyPost, HistoryEntry, Post};
+
+impl AnyPost {
+    pub fn id(&self) -> u64 {
+        self.record().id
+    }
+
+    pub fn record(&self) -> PostRecord<'_> {
+        match self {
+            Self::New(post) => post.record(),
+            Self::Draft(post) => post.record(),
+            Self::Unmoderated(post) => post.record(),
+            Self::Published(post) => post.record(),
+            Self::Archived(post) => post.record(),
+            Self::Deleted(post) => post.record(),
+            Self::Restored(post) => post.record(),
+        }
+    }
+
+    /// Restores the post from its stored `record`.
+    pub fn from_record(record: PostRecord<'_>) -> Result<Self, UnknownState> {
+        let PostRecord {
+            id,
+            title,
+            body,
+            state,
+            history,
+        } = record;
+        let post = Post::<state::New>::from_parts(
+            id,
+            title.into_owned(),
+            body.into_owned(),
+            history.into_owned(),
+        );
+        Self::retag(post, &state).map_err(|_| UnknownState(state.into_owned()))
+    }
+}
+
+impl Serialize for AnyPost {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.record().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for AnyPost {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Self::from_record(PostRecord::deserialize(deserializer)?).map_err(de::Error::custom)
+    }
+}
+
+/// Stored representation of a [`Post`] in any state
+///
+/// It can only be obtained from an existing post or by deserializing it, so
+/// posts can't skip their transitions by being stored.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PostRecord<'a> {
+    pub(crate) id: u64,
+    pub(crate) title: Cow<'a, str>,
+    pub(crate) body: Cow<'a, str>,
+    pub(crate) state: Cow<'a, str>,
+    #[serde(default)]
+    pub(crate) history: Cow<'a, [HistoryEntry]>,
+}
+
+impl PostRecord<'_> {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+
+    /// Returns the [`State::TAG`] of the post's state.
+    pub fn state(&self) -> &str {
+        &self.state
+    }
+
+    pub fn history(&self) -> &[HistoryEntry] {
+        &self.history
+    }
+
+    pub fn into_owned(self) -> PostRecord<'static> {
+        PostRecord {
+            id: self.id,
+            title: Cow::Owned(self.title.into_owned()),
+            body: Cow::Owned(self.body.into_owned()),
+            state: Cow::Owned(self.state.into_owned()),
+            history: Cow::Owned(self.history.into_owned()),
+        }
+    }
+}
+
+/// Error of restoring a post whose state tag isn't known
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownState(pub String);
+
+impl fmt::Display for UnknownState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown post state `{}`", self.0)
+    }
+}
+
+impl error::Error for UnknownState {}
+
+/// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_round_trip() {
+        let post = Post::new(1, "Title".to_string(), "Body".to_string())
+            .publish("author")
+            .deny("spam", "moderator");
+        let history = post.history().to_vec();
+
+        let json = serde_json::to_string(&AnyPost::from(post)).unwrap();
+        assert!(json.starts_with(r#"{"id":1,"title":"Title","body":"Body","state":"deleted","#));
+
+        let AnyPost::Deleted(post) = serde_json::from_str(&json).unwrap() else {
+            panic!("post should be deleted");
+        };
+        assert_eq!(post.history(), history);
+        let post = post.restore("admin");
+        assert_eq!(post.title(), "Title");
+        assert_eq!(post.history().len(), 3);
+    }
+
+    #[test]
+    fn test_missing_history_is_empty() {
+        let json = r#"{"id":1,"title":"Title","body":"Body","state":"published"}"#;
+        let AnyPost::Published(post) = serde_json::from_str(json).unwrap() else {
+            panic!("post should be published");
+        };
+        assert!(post.history().is_empty());
+    }
+
+    #[test]
+    fn test_unknown_state_is_rejected() {
+        let json = r#"{"id":1,"title":"Title","body":"Body","state":"pending"}"#;
+        let err = serde_json::from_str::<AnyPost>(json).err().unwrap();
+        assert!(err.to_string().contains("unknown post state `pending`"));
+    }
+
+    #[test]
+    fn test_try_into_concrete_state() {
+        let post = AnyPost::from(Post::new(1, "Title".to_string(), "Body".to_string()));
+        assert_eq!(post.tag(), "new");
+
+        let post = Post::<state::Published>::try_from(post).err().unwrap();
+        let post: Post<state::New> = post.try_into().ok().unwrap();
+        assert_eq!(post.id(), 1);
+    }
+}
@@ -0,0 +1,43 @@
+use std::borrow::Cow;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+/// Entry of a post's history, recording one of its state transitions
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    /// [`State::TAG`] of the state the post left
+    ///
+    /// [`State::TAG`]: crate::state::State::TAG
+    pub from: Cow<'static, str>,
+    /// [`State::TAG`] of the state the post entered
+    ///
+    /// [`State::TAG`]: crate::state::State::TAG
+    pub to: Cow<'static, str>,
+    /// Who performed the transition
+    pub actor: String,
+    /// Why the transition was performed, if it requires a reason
+    pub reason: Option<String>,
+    /// When the transition was performed, in seconds since the Unix epoch
+    pub at: u64,
+}
+
+impl HistoryEntry {
+    pub(crate) fn now(
+        from: &'static str,
+        to: &'static str,
+        actor: String,
+        reason: Option<String>,
+    ) -> Self {
+        let at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        Self {
+            from: Cow::Borrowed(from),
+            to: Cow::Borrowed(to),
+            actor,
+            reason,
+            at,
+        }
+    }
+}
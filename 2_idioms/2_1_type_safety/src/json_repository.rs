@@ -0,0 +1,93 @@
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use crate::{AnyPost, PostRecord, PostRepository};
+
+/// [`PostRepository`] keeping posts in a JSON file
+///
+/// The whole file is loaded on opening and rewritten on every change, so it
+/// suits small amounts of posts only.
+pub struct JsonPostRepository {
+    path: PathBuf,
+    posts: BTreeMap<u64, PostRecord<'static>>,
+}
+
+impl JsonPostRepository {
+    /// Opens the file at `path`, which is created on the first save if it
+    /// doesn't exist.
+    pub fn open(path: impl Into<PathBuf>) -> io::Result<Self> {
+        let path = path.into();
+        let posts = match File::open(&path) {
+            Ok(file) => serde_json::from_reader::<_, Vec<AnyPost>>(BufReader::new(file))?
+                .iter()
+                .map(|post| (post.id(), post.record().into_owned()))
+                .collect(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(Self { path, posts })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Replaces the file atomically, so it's never left half-written.
+    fn flush(&self) -> io::Result<()> {
+        let tmp = self.path.with_extension("json.tmp");
+        let mut file = BufWriter::new(File::create(&tmp)?);
+        serde_json::to_writer_pretty(&mut file, &self.posts.values().collect::<Vec<_>>())?;
+        file.into_inner()?.sync_all()?;
+        fs::rename(&tmp, &self.path)
+    }
+}
+
+// Records are validated on opening and saving, so this fails only if the
+// validation is bypassed somehow.
+fn restore(record: &PostRecord<'_>) -> io::Result<AnyPost> {
+    AnyPost::from_record(record.clone()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+impl PostRepository for JsonPostRepository {
+    type Error = io::Error;
+
+    fn save(&mut self, post: PostRecord<'_>) -> io::Result<()> {
+        restore(&post)?;
+        let id = post.id;
+        let prev = self.posts.insert(id, post.into_owned());
+        if let Err(e) = self.flush() {
+            // Keep the posts in memory consistent with the file.
+            match prev {
+                Some(prev) => self.posts.insert(id, prev),
+                None => self.posts.remove(&id),
+            };
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn load(&self, id: u64) -> io::Result<Option<AnyPost>> {
+        self.posts.get(&id).map(restore).transpose()
+    }
+
+    fn remove(&mut self, id: u64) -> io::Result<Option<AnyPost>> {
+        let Some(record) = self.posts.remove(&id) else {
+            return Ok(None);
+        };
+        if let Err(e) = self.flush() {
+            self.posts.insert(id, record);
+            return Err(e);
+        }
+        restore(&record).map(Some)
+    }
+
+    fn find_by_state(&self, tag: &str) -> io::Result<Vec<AnyPost>> {
+        self.posts
+            .values()
+            .filter(|record| record.state == tag)
+            .map(restore)
+            .collect()
+    }
+}
@@ -0,0 +1,285 @@
+use std::borrow::Cow;
+use std::marker::PhantomData;
+
+use task_2_1_derive::Typestate;
+
+use self::state::{State, TransitionTo};
+
+pub mod any_post;
+pub mod history;
+pub mod json_repository;
+pub mod repository;
+pub mod sqlite_repository;
+
+pub use self::{
+    any_post::{PostRecord, UnknownState},
+    history::HistoryEntry,
+    json_repository::JsonPostRepository,
+    repository::PostRepository,
+    sqlite_repository::SqlitePostRepository,
+};
+
+#[derive(Typestate)]
+#[typestate(
+    erased = AnyPost,
+    states(New, Draft, Unmoderated, Published, Archived, Deleted, Restored),
+    transitions(
+        New -> Draft,
+        New -> Unmoderated,
+        Draft -> Unmoderated,
+        Unmoderated -> Published,
+        Unmoderated -> Deleted,
+        Published -> Archived,
+        Published -> Deleted,
+        Archived -> Deleted,
+        Deleted -> Restored,
+        Restored -> Unmoderated,
+    ),
+)]
+pub struct Post<State> {
+    id: u64,
+    title: String,
+    body: String,
+    history: Vec<HistoryEntry>,
+    state: PhantomData<State>,
+}
+
+impl<S> Post<S> {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+
+    /// Returns all the transitions of this post, oldest first.
+    pub fn history(&self) -> &[HistoryEntry] {
+        &self.history
+    }
+
+    /// Restores a stored post, without going through the transitions.
+    pub(crate) fn from_parts(
+        id: u64,
+        title: String,
+        body: String,
+        history: Vec<HistoryEntry>,
+    ) -> Self {
+        Self {
+            id,
+            title,
+            body,
+            history,
+            state: PhantomData,
+        }
+    }
+}
+
+impl<S: State> Post<S> {
+    /// Returns the representation of this post to be stored.
+    pub fn record(&self) -> PostRecord<'_> {
+        PostRecord {
+            id: self.id,
+            title: Cow::Borrowed(&self.title),
+            body: Cow::Borrowed(&self.body),
+            state: Cow::Borrowed(S::TAG),
+            history: Cow::Borrowed(&self.history),
+        }
+    }
+
+    /// Performs the transition on behalf of the `actor`, recording it in the
+    /// history.
+    fn transition_by<T>(mut self, actor: String, reason: Option<String>) -> Post<T>
+    where
+        S: TransitionTo<T>,
+        T: State,
+    {
+        let entry = HistoryEntry::now(S::TAG, T::TAG, actor, reason);
+        self.history.push(entry);
+        self.transition()
+    }
+}
+
+impl Post<state::New> {
+    pub fn new(id: u64, title: String, body: String) -> Self {
+        Self::from_parts(id, title, body, Vec::new())
+    }
+
+    /// Transition from `New` to `Draft`
+    pub fn draft(self, actor: impl Into<String>) -> Post<state::Draft> {
+        self.transition_by(actor.into(), None)
+    }
+
+    /// Transition from `New` to `Unmoderated`
+    pub fn publish(self, actor: impl Into<String>) -> Post<state::Unmoderated> {
+        self.transition_by(actor.into(), None)
+    }
+}
+
+impl Post<state::Draft> {
+    pub fn set_title(&mut self, title: String) {
+        self.title = title;
+    }
+
+    pub fn set_body(&mut self, body: String) {
+        self.body = body;
+    }
+
+    /// Transition from `Draft` to `Unmoderated`
+    pub fn publish(self, actor: impl Into<String>) -> Post<state::Unmoderated> {
+        self.transition_by(actor.into(), None)
+    }
+}
+
+impl Post<state::Unmoderated> {
+    /// Transition from `Unmoderated` to `Published`
+    pub fn allow(self, actor: impl Into<String>) -> Post<state::Published> {
+        self.transition_by(actor.into(), None)
+    }
+
+    /// Transition from `Unmoderated` to `Deleted`
+    pub fn deny(self, reason: impl Into<String>, actor: impl Into<String>) -> Post<state::Deleted> {
+        self.transition_by(actor.into(), Some(reason.into()))
+    }
+}
+
+impl Post<state::Published> {
+    /// Transition from `Published` to `Archived`
+    pub fn archive(self, actor: impl Into<String>) -> Post<state::Archived> {
+        self.transition_by(actor.into(), None)
+    }
+
+    /// Transition from `Published` to `Deleted`
+    pub fn delete(
+        self,
+        reason: impl Into<String>,
+        actor: impl Into<String>,
+    ) -> Post<state::Deleted> {
+        self.transition_by(actor.into(), Some(reason.into()))
+    }
+}
+
+impl Post<state::Archived> {
+    /// Transition from `Archived` to `Deleted`
+    pub fn delete(
+        self,
+        reason: impl Into<String>,
+        actor: impl Into<String>,
+    ) -> Post<state::Deleted> {
+        self.transition_by(actor.into(), Some(reason.into()))
+    }
+}
+
+impl Post<state::Deleted> {
+    /// Transition from `Deleted` to `Restored`
+    pub fn restore(self, actor: impl Into<String>) -> Post<state::Restored> {
+        self.transition_by(actor.into(), None)
+    }
+}
+
+impl Post<state::Restored> {
+    /// Transition from `Restored` back to `Unmoderated`
+    pub fn resubmit(self, actor: impl Into<String>) -> Post<state::Unmoderated> {
+        self.transition_by(actor.into(), None)
+    }
+}
+
+/// Tests
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_post_lifecycle() {
+        let new_post = Post::new(1, "Title".to_string(), "Body".to_string());
+        let unmoderated_post = new_post.publish("author");
+        let published_post = unmoderated_post.allow("moderator");
+        let archived_post = published_post.archive("moderator");
+        let deleted_post = archived_post.delete("outdated", "admin");
+
+        let transitions: Vec<_> = deleted_post
+            .history()
+            .iter()
+            .map(|e| (&*e.from, &*e.to, &*e.actor))
+            .collect();
+        assert_eq!(
+            transitions,
+            [
+                ("new", "unmoderated", "author"),
+                ("unmoderated", "published", "moderator"),
+                ("published", "archived", "moderator"),
+                ("archived", "deleted", "admin"),
+            ]
+        );
+
+        // Uncommenting the following line would cause a compile-time error:
+        // let invalid_transition = new_post.delete("spam", "moderator");
+    }
+
+    #[test]
+    fn test_deny_unmoderated_post() {
+        let new_post = Post::new(2, "Another Title".to_string(), "Another Body".to_string());
+        let unmoderated_post = new_post.publish("author");
+        let deleted_post = unmoderated_post.deny("spam", "moderator");
+
+        let entry = deleted_post.history().last().unwrap();
+        assert_eq!(entry.actor, "moderator");
+        assert_eq!(entry.reason.as_deref(), Some("spam"));
+
+        // Uncommenting the following line would cause a compile-time error:
+        // let invalid_transition = deleted_post.deny("spam", "moderator");
+    }
+
+    #[test]
+    fn test_edit_draft() {
+        let mut draft = Post::new(3, "Title".to_string(), "Body".to_string()).draft("author");
+        draft.set_title("Better Title".to_string());
+        draft.set_body("Better Body".to_string());
+        let unmoderated_post = draft.publish("author");
+
+        assert_eq!(unmoderated_post.title(), "Better Title");
+        assert_eq!(unmoderated_post.body(), "Better Body");
+        assert_eq!(unmoderated_post.history().len(), 2);
+
+        // Uncommenting the following line would cause a compile-time error:
+        // unmoderated_post.set_title("Sneaky Title".to_string());
+    }
+
+    #[test]
+    fn test_restore_deleted_post() {
+        let deleted_post = Post::new(4, "Title".to_string(), "Body".to_string())
+            .publish("author")
+            .deny("off-topic", "moderator");
+        let unmoderated_post = deleted_post.restore("admin").resubmit("author");
+        let published_post = unmoderated_post.allow("moderator");
+
+        let tags: Vec<_> = published_post.history().iter().map(|e| &*e.to).collect();
+        assert_eq!(
+            tags,
+            [
+                "unmoderated",
+                "deleted",
+                "restored",
+                "unmoderated",
+                "published"
+            ]
+        );
+        assert!(published_post.history()[1].reason.is_some());
+        assert!(published_post.history()[2].reason.is_none());
+    }
+
+    #[test]
+    fn test_record_carries_state_tag() {
+        let post = Post::new(5, "Title".to_string(), "Body".to_string());
+        assert_eq!(post.record().state(), "new");
+        let post = post.publish("author").allow("moderator");
+        assert_eq!(post.record().state(), "published");
+        let post = post.delete("spam", "moderator");
+        assert_eq!(post.record().state(), "deleted");
+        assert_eq!(post.record().history().len(), 3);
+    }
+}
@@ -0,0 +1,30 @@
+use crate::state::State;
+use crate::{AnyPost, Post, PostRecord};
+
+/// Storage of posts in any state
+pub trait PostRepository {
+    type Error;
+
+    /// Saves the post, replacing the stored one with the same ID.
+    fn save(&mut self, post: PostRecord<'_>) -> Result<(), Self::Error>;
+
+    fn load(&self, id: u64) -> Result<Option<AnyPost>, Self::Error>;
+
+    fn remove(&mut self, id: u64) -> Result<Option<AnyPost>, Self::Error>;
+
+    /// Returns all the posts in the state with the given [`State::TAG`],
+    /// ordered by ID.
+    fn find_by_state(&self, tag: &str) -> Result<Vec<AnyPost>, Self::Error>;
+
+    /// Returns all the posts in the `S` state, ordered by ID.
+    fn find<S: State>(&self) -> Result<Vec<Post<S>>, Self::Error>
+    where
+        Self: Sized,
+    {
+        let posts = self.find_by_state(S::TAG)?;
+        Ok(posts
+            .into_iter()
+            .filter_map(|p| S::from_erased(p).ok())
+            .collect())
+    }
+}
@@ -0,0 +1,149 @@
+use std::path::Path;
+
+use rusqlite::types::Type;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+use crate::{AnyPost, PostRecord, PostRepository};
+
+/// Change of the `posts` table, following one of the [`PostRecord`] shape
+struct Migration {
+    /// Schema version of the databases the migration has been applied to.
+    version: u32,
+    /// What the migration does, reported if it fails.
+    name: &'static str,
+    sql: &'static str,
+}
+
+/// Migrations of the `posts` table by increasing versions.
+///
+/// Existing databases record the version they're at, so released migrations
+/// are never changed, and new ones get the next version.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "create posts indexed by state",
+        sql: "CREATE TABLE posts (
+            id INTEGER PRIMARY KEY NOT NULL,
+            title TEXT NOT NULL,
+            body TEXT NOT NULL,
+            state TEXT NOT NULL
+        );
+        CREATE INDEX posts_state ON posts (state);",
+    },
+    Migration {
+        version: 2,
+        name: "add post histories",
+        sql: "ALTER TABLE posts ADD COLUMN history TEXT NOT NULL DEFAULT '[]'",
+    },
+];
+
+/// [`PostRepository`] over an embedded SQLite database
+///
+/// Post states are stored as their [`State::TAG`]s, and their histories as
+/// JSON arrays.
+///
+/// [`State::TAG`]: crate::state::State::TAG
+pub struct SqlitePostRepository {
+    conn: Connection,
+}
+
+impl SqlitePostRepository {
+    /// Opens the posts database at `path`, upgrading its schema if outdated.
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        Self::with_connection(Connection::open(path)?)
+    }
+
+    /// Opens an empty posts database, discarded once dropped.
+    pub fn open_in_memory() -> rusqlite::Result<Self> {
+        Self::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(mut conn: Connection) -> rusqlite::Result<Self> {
+        migrate(&mut conn)?;
+        Ok(Self { conn })
+    }
+}
+
+/// Applies the [`MIGRATIONS`] newer than the schema version, which is kept in
+/// SQLite's `user_version` pragma, each one in its own transaction.
+fn migrate(conn: &mut Connection) -> rusqlite::Result<()> {
+    let version: u32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    for migration in MIGRATIONS.iter().filter(|m| m.version > version) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration.sql).map_err(|e| match e {
+            rusqlite::Error::SqliteFailure(code, msg) => {
+                let msg = msg.unwrap_or_else(|| code.to_string());
+                let msg = format!(
+                    "posts migration {} ({}): {msg}",
+                    migration.version, migration.name
+                );
+                rusqlite::Error::SqliteFailure(code, Some(msg))
+            }
+            e => e,
+        })?;
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+/// Restores a post from an `(id, title, body, state, history)` row.
+fn read_post(row: &Row<'_>) -> rusqlite::Result<AnyPost> {
+    let history = row.get::<_, String>(4)?;
+    let record = PostRecord {
+        id: row.get(0)?,
+        title: row.get::<_, String>(1)?.into(),
+        body: row.get::<_, String>(2)?.into(),
+        state: row.get::<_, String>(3)?.into(),
+        history: serde_json::from_str::<Vec<_>>(&history)
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(4, Type::Text, Box::new(e)))?
+            .into(),
+    };
+    AnyPost::from_record(record)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(3, Type::Text, Box::new(e)))
+}
+
+impl PostRepository for SqlitePostRepository {
+    type Error = rusqlite::Error;
+
+    fn save(&mut self, post: PostRecord<'_>) -> rusqlite::Result<()> {
+        let history = serde_json::to_string(&post.history)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        self.conn.execute(
+            "INSERT INTO posts (id, title, body, state, history) VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT (id) DO UPDATE
+             SET title = excluded.title, body = excluded.body, state = excluded.state,
+                 history = excluded.history",
+            params![post.id, post.title, post.body, post.state, history],
+        )?;
+        Ok(())
+    }
+
+    fn load(&self, id: u64) -> rusqlite::Result<Option<AnyPost>> {
+        self.conn
+            .query_row(
+                "SELECT id, title, body, state, history FROM posts WHERE id = ?1",
+                [id],
+                read_post,
+            )
+            .optional()
+    }
+
+    fn remove(&mut self, id: u64) -> rusqlite::Result<Option<AnyPost>> {
+        self.conn
+            .query_row(
+                "DELETE FROM posts WHERE id = ?1 RETURNING id, title, body, state, history",
+                [id],
+                read_post,
+            )
+            .optional()
+    }
+
+    fn find_by_state(&self, tag: &str) -> rusqlite::Result<Vec<AnyPost>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, title, body, state, history FROM posts WHERE state = ?1 ORDER BY id",
+        )?;
+        let posts = stmt.query_map([tag], read_post)?.collect();
+        posts
+    }
+}
@@ -0,0 +1,133 @@
+use std::io;
+
+use task_2_1::{
+    state, AnyPost, JsonPostRepository, Post, PostRecord, PostRepository, SqlitePostRepository,
+};
+
+fn post(id: u64) -> Post<state::New> {
+    Post::new(id, format!("Title {id}"), format!("Body {id}"))
+}
+
+// Runs the same scenario against every backend.
+fn round_trip<R>(repo: &mut R)
+where
+    R: PostRepository,
+    R::Error: std::fmt::Debug,
+{
+    repo.save(post(1).record()).unwrap();
+    repo.save(post(2).publish("author").record()).unwrap();
+    repo.save(post(3).publish("author").allow("moderator").record())
+        .unwrap();
+    repo.save(post(4).publish("author").deny("spam", "moderator").record())
+        .unwrap();
+
+    let Some(AnyPost::Unmoderated(unmoderated)) = repo.load(2).unwrap() else {
+        panic!("post 2 should be unmoderated");
+    };
+    assert_eq!(unmoderated.title(), "Title 2");
+    repo.save(unmoderated.allow("moderator").record()).unwrap();
+
+    let published = repo.find::<state::Published>().unwrap();
+    let ids: Vec<_> = published.iter().map(Post::id).collect();
+    assert_eq!(ids, [2, 3]);
+    assert_eq!(repo.find_by_state("new").unwrap().len(), 1);
+    assert!(repo.find::<state::Unmoderated>().unwrap().is_empty());
+
+    let Some(AnyPost::Deleted(deleted)) = repo.remove(4).unwrap() else {
+        panic!("post 4 should be deleted");
+    };
+    let entry = deleted.history().last().unwrap();
+    assert_eq!(entry.reason.as_deref(), Some("spam"));
+    assert_eq!(entry.actor, "moderator");
+    repo.save(deleted.restore("admin").record()).unwrap();
+    assert_eq!(repo.load(4).unwrap().unwrap().tag(), "restored");
+    repo.remove(4).unwrap();
+    assert!(repo.load(4).unwrap().is_none());
+    assert!(repo.remove(4).unwrap().is_none());
+}
+
+#[test]
+fn test_json_repository() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("posts.json");
+
+    round_trip(&mut JsonPostRepository::open(&path).unwrap());
+
+    let repo = JsonPostRepository::open(&path).unwrap();
+    assert!(matches!(repo.load(3).unwrap(), Some(AnyPost::Published(_))));
+    assert!(matches!(repo.load(1).unwrap(), Some(AnyPost::New(_))));
+}
+
+#[test]
+fn test_json_repository_rejects_unknown_state() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("posts.json");
+    std::fs::write(
+        &path,
+        r#"[{"id":1,"title":"Title","body":"Body","state":"pending"}]"#,
+    )
+    .unwrap();
+
+    assert!(JsonPostRepository::open(&path).is_err());
+}
+
+#[test]
+fn test_json_repository_refuses_to_save_unknown_state() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("posts.json");
+    let mut repo = JsonPostRepository::open(&path).unwrap();
+    repo.save(post(1).record()).unwrap();
+
+    let record: PostRecord<'_> =
+        serde_json::from_str(r#"{"id":2,"title":"Title","body":"Body","state":"pending"}"#)
+            .unwrap();
+    let err = repo.save(record).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+    assert!(repo.load(2).unwrap().is_none());
+    assert_eq!(repo.find_by_state("new").unwrap().len(), 1);
+    let repo = JsonPostRepository::open(&path).unwrap();
+    assert!(repo.load(2).unwrap().is_none());
+    assert!(matches!(repo.load(1).unwrap(), Some(AnyPost::New(_))));
+}
+
+#[test]
+fn test_sqlite_repository() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("posts.db");
+
+    round_trip(&mut SqlitePostRepository::open(&path).unwrap());
+
+    let repo = SqlitePostRepository::open(&path).unwrap();
+    assert!(matches!(repo.load(3).unwrap(), Some(AnyPost::Published(_))));
+    assert_eq!(repo.find_by_state("unknown").unwrap().len(), 0);
+}
+
+#[test]
+fn test_sqlite_repository_migrates_posts_without_history() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("posts.db");
+    let conn = rusqlite::Connection::open(&path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE posts (
+            id INTEGER PRIMARY KEY NOT NULL,
+            title TEXT NOT NULL,
+            body TEXT NOT NULL,
+            state TEXT NOT NULL
+        );
+        CREATE INDEX posts_state ON posts (state);
+        INSERT INTO posts VALUES (1, 'Title 1', 'Body 1', 'published');
+        PRAGMA user_version = 1;",
+    )
+    .unwrap();
+    drop(conn);
+
+    let repo = SqlitePostRepository::open(&path).unwrap();
+    let Some(AnyPost::Published(published)) = repo.load(1).unwrap() else {
+        panic!("post 1 should be published");
+    };
+    assert!(published.history().is_empty());
+
+    let repo = SqlitePostRepository::open(&path).unwrap();
+    assert_eq!(repo.find::<state::Published>().unwrap().len(), 1);
+}
@@ -0,0 +1,204 @@
+use std::collections::HashSet;
+use std::hash::Hash;
+
+use crate::Cyclic;
+
+/// Sequence of `N` items, which is considered the same in any of its
+/// rotations
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Cycle<T, const N: usize>([T; N]);
+
+impl<T, const N: usize> Cycle<T, N> {
+    pub fn new(items: [T; N]) -> Self {
+        Self(items)
+    }
+
+    pub fn as_array(&self) -> &[T; N] {
+        &self.0
+    }
+
+    pub fn into_array(self) -> [T; N] {
+        self.0
+    }
+
+    /// Rotates the items `k` positions to the left, so the item at `k` becomes
+    /// the first one.
+    pub fn rotate_left(&mut self, k: usize) {
+        if N > 0 {
+            self.0.rotate_left(k % N);
+        }
+    }
+}
+
+impl<T: Ord, const N: usize> Cycle<T, N> {
+    /// Returns how many times the items have to be rotated to the left to
+    /// become the lexicographically least rotation.
+    ///
+    /// Uses Booth's algorithm, so it takes O(N) time.
+    pub fn canonical_rotation(&self) -> usize {
+        least_rotation(&self.0)
+    }
+
+    /// Returns the lexicographically least rotation, which is the same for
+    /// all the rotations of a cycle. So it can be hashed or compared to
+    /// deduplicate and match cycles regardless of their rotation.
+    pub fn canonical(&self) -> Self
+    where
+        T: Clone,
+    {
+        let mut canonical = self.clone();
+        canonical.rotate_left(self.canonical_rotation());
+        canonical
+    }
+}
+
+impl<T: Ord, const N: usize> Cyclic for Cycle<T, N> {
+    /// Compares the canonical rotations of both cycles, so it takes O(N)
+    /// time.
+    fn rotations_to(&self, other: &Self) -> Option<usize> {
+        // Empty cycles are trivially equal.
+        if N == 0 {
+            return Some(0);
+        }
+        let (from, to) = (self.canonical_rotation(), other.canonical_rotation());
+        (0..N)
+            .all(|i| self.0[(from + i) % N] == other.0[(to + i) % N])
+            .then(|| (from + N - to) % N)
+    }
+}
+
+/// Removes the items being rotations of the preceding ones, keeping the
+/// order of the remaining ones.
+pub fn dedup_rotations<T, const N: usize>(items: &mut Vec<Cycle<T, N>>)
+where
+    T: Clone + Eq + Hash + Ord,
+{
+    let mut seen = HashSet::with_capacity(items.len());
+    items.retain(|item| seen.insert(item.canonical()));
+}
+
+/// Booth's algorithm, returning the start of the lexicographically least
+/// rotation of `s`.
+///
+/// It runs the Knuth-Morris-Pratt failure function over `s` concatenated with
+/// itself, moving the candidate start `k` whenever a lesser rotation is
+/// found.
+fn least_rotation<T: Ord>(s: &[T]) -> usize {
+    let n = s.len();
+    let at = |i: usize| &s[i % n];
+    // `None` stands for the `-1` failure value.
+    let mut failure: Vec<Option<usize>> = vec![None; 2 * n];
+    let mut k = 0;
+    for j in 1..2 * n {
+        let mut i = failure[j - k - 1];
+        while let Some(prev) = i {
+            if at(j) == at(k + prev + 1) {
+                break;
+            }
+            if at(j) < at(k + prev + 1) {
+                k = j - prev - 1;
+            }
+            i = failure[prev];
+        }
+        match i {
+            None if at(j) != at(k) => {
+                if at(j) < at(k) {
+                    k = j;
+                }
+                failure[j - k] = None;
+            }
+            None => failure[j - k] = Some(0),
+            Some(prev) => failure[j - k] = Some(prev + 1),
+        }
+    }
+    k
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Checks against the naive O(N^2) computation.
+    fn naive_least_rotation<T: Ord + Clone>(s: &[T]) -> Vec<T> {
+        (0..s.len())
+            .map(|k| {
+                let mut r = s.to_vec();
+                r.rotate_left(k);
+                r
+            })
+            .min()
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_canonical_rotation_matches_naive() {
+        // Small alphabet produces plenty of repetitions and periodic cycles.
+        let mut seed = 7_u32;
+        for _ in 0..500 {
+            let mut items = [0_u8; 8];
+            for item in &mut items {
+                seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                *item = (seed >> 16) as u8 % 3;
+            }
+            let cycle = Cycle::new(items);
+            assert_eq!(
+                cycle.canonical().as_array().to_vec(),
+                naive_least_rotation(&items),
+                "{items:?}",
+            );
+        }
+    }
+
+    #[test]
+    fn test_canonical_rotation() {
+        assert_eq!(Cycle::new([3, 1, 2]).canonical_rotation(), 1);
+        assert_eq!(Cycle::new(*b"bbaab").canonical(), Cycle::new(*b"aabbb"));
+        assert_eq!(Cycle::new([1, 1, 1]).canonical(), Cycle::new([1, 1, 1]));
+        assert_eq!(Cycle::<u8, 0>::new([]).canonical_rotation(), 0);
+    }
+
+    #[test]
+    fn test_rotations_to() {
+        let cycle = Cycle::new([1, 2, 3, 4]);
+        assert_eq!(cycle.rotations_to(&Cycle::new([1, 2, 3, 4])), Some(0));
+        assert_eq!(cycle.rotations_to(&Cycle::new([3, 4, 1, 2])), Some(2));
+        assert_eq!(cycle.rotations_to(&Cycle::new([4, 3, 2, 1])), None);
+
+        let mut rotated = cycle;
+        rotated.rotate_left(cycle.rotations_to(&Cycle::new([4, 1, 2, 3])).unwrap());
+        assert_eq!(rotated, Cycle::new([4, 1, 2, 3]));
+
+        let periodic = Cycle::new([1, 2, 1, 2]);
+        assert_eq!(
+            periodic
+                .rotations_to(&Cycle::new([2, 1, 2, 1]))
+                .map(|k| k % 2),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn test_empty_cycle() {
+        let mut empty = Cycle::<u8, 0>::new([]);
+        empty.rotate_left(3);
+        assert_eq!(empty.canonical_rotation(), 0);
+        assert_eq!(empty.canonical(), empty);
+        assert_eq!(empty.rotations_to(&Cycle::new([])), Some(0));
+    }
+
+    #[test]
+    fn test_dedup_rotations() {
+        let mut items = vec![
+            Cycle::new(['a', 'b', 'c']),
+            Cycle::new(['b', 'c', 'a']),
+            Cycle::new(['c', 'b', 'a']),
+            Cycle::new(['a', 'c', 'b']),
+            Cycle::new(['c', 'a', 'b']),
+        ];
+        dedup_rotations(&mut items);
+        assert_eq!(
+            items,
+            [Cycle::new(['a', 'b', 'c']), Cycle::new(['c', 'b', 'a'])]
+        );
+    }
+}
@@ -0,0 +1,258 @@
+use std::mem;
+
+use rayon::iter::{Either, IntoParallelIterator, ParallelIterator};
+
+pub mod cycle;
+
+pub use self::cycle::{dedup_rotations, Cycle};
+
+/// Sequence equal to another one up to a rotation
+pub trait Cyclic {
+    /// Returns how many times `self` has to be rotated to become equal to
+    /// `other`, if it can be at all.
+    fn rotations_to(&self, other: &Self) -> Option<usize>;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trinity<T> {
+    pub a: T,
+    pub b: T,
+    pub c: T,
+}
+
+impl<T: Clone> Trinity<T> {
+    pub fn rotate(&mut self) {
+        mem::swap(&mut self.a, &mut self.b);
+        mem::swap(&mut self.b, &mut self.c);
+    }
+}
+
+impl<T: PartialEq> Cyclic for Trinity<T> {
+    fn rotations_to(&self, other: &Self) -> Option<usize> {
+        let Self { a, b, c } = self;
+        [(a, b, c), (b, c, a), (c, a, b)]
+            .into_iter()
+            .position(|(a, b, c)| (a, b, c) == (&other.a, &other.b, &other.c))
+    }
+}
+
+#[derive(Debug)]
+pub struct Solver<C> {
+    pub expected: C,
+    pub unsolved: Vec<C>,
+}
+
+/// Item matching the expected one
+#[derive(Clone, Debug, PartialEq)]
+pub struct Solved<C> {
+    /// Item in its original orientation
+    pub item: C,
+    /// How many times the item has to be rotated to match the expected one
+    pub rotations: usize,
+}
+
+/// Outcome of resolving a Solver
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report<C> {
+    /// Items removed from `unsolved`, in their original order
+    pub solved: Vec<Solved<C>>,
+    /// Number of items left in `unsolved`
+    pub unsolved: usize,
+}
+
+impl<C: Cyclic> Solver<C> {
+    /// Removes any item from `unsolved` that matches `expected` in *any* of its rotations.
+    ///
+    /// The items are left in their original orientation, and the removed ones
+    /// are reported.
+    pub fn resolve(&mut self) -> Report<C> {
+        let (mut solved, mut unsolved) = (Vec::new(), Vec::new());
+        for item in mem::take(&mut self.unsolved) {
+            match self.check(item) {
+                Either::Left(item) => solved.push(item),
+                Either::Right(item) => unsolved.push(item),
+            }
+        }
+        self.finish(solved, unsolved)
+    }
+
+    /// Same as `resolve`, but checks the items in parallel on the current
+    /// rayon thread pool, so it's worth it for large `unsolved` vectors only.
+    ///
+    /// Use `ThreadPool::install` to run it on a specific pool.
+    pub fn resolve_par(&mut self) -> Report<C>
+    where
+        C: Send + Sync,
+    {
+        let (solved, unsolved) = mem::take(&mut self.unsolved)
+            .into_par_iter()
+            .partition_map(|item| self.check(item));
+        self.finish(solved, unsolved)
+    }
+
+    fn check(&self, item: C) -> Either<Solved<C>, C> {
+        match item.rotations_to(&self.expected) {
+            Some(rotations) => Either::Left(Solved { item, rotations }),
+            None => Either::Right(item),
+        }
+    }
+
+    fn finish(&mut self, solved: Vec<Solved<C>>, unsolved: Vec<C>) -> Report<C> {
+        self.unsolved = unsolved;
+        Report {
+            solved,
+            unsolved: self.unsolved.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trinity_rotate() {
+        let mut t = Trinity { a: 1, b: 2, c: 3 };
+        t.rotate();
+        assert_eq!(t, Trinity { a: 2, b: 3, c: 1 });
+
+        t.rotate();
+        assert_eq!(t, Trinity { a: 3, b: 1, c: 2 });
+
+        t.rotate();
+        assert_eq!(t, Trinity { a: 1, b: 2, c: 3 });
+    }
+
+    #[test]
+    fn test_trinity_rotations_to() {
+        let t = Trinity { a: 1, b: 2, c: 3 };
+        assert_eq!(t.rotations_to(&Trinity { a: 1, b: 2, c: 3 }), Some(0));
+        assert_eq!(t.rotations_to(&Trinity { a: 2, b: 3, c: 1 }), Some(1));
+        assert_eq!(t.rotations_to(&Trinity { a: 3, b: 1, c: 2 }), Some(2));
+        assert_eq!(t.rotations_to(&Trinity { a: 2, b: 1, c: 3 }), None);
+    }
+
+    #[test]
+    fn test_solver_resolve() {
+        let mut solver = Solver {
+            expected: Trinity { a: 1, b: 2, c: 3 },
+            unsolved: vec![
+                Trinity { a: 1, b: 2, c: 3 },
+                Trinity { a: 2, b: 1, c: 3 },
+                Trinity { a: 2, b: 3, c: 1 },
+                Trinity { a: 3, b: 1, c: 2 },
+            ],
+        };
+
+        let report = solver.resolve();
+
+        assert_eq!(solver.unsolved, vec![Trinity { a: 2, b: 1, c: 3 },]);
+        let solved: Vec<_> = report
+            .solved
+            .into_iter()
+            .map(|s| (s.item, s.rotations))
+            .collect();
+        assert_eq!(
+            solved,
+            vec![
+                (Trinity { a: 1, b: 2, c: 3 }, 0),
+                (Trinity { a: 2, b: 3, c: 1 }, 2),
+                (Trinity { a: 3, b: 1, c: 2 }, 1),
+            ]
+        );
+        assert_eq!(report.unsolved, 1);
+    }
+
+    #[test]
+    fn test_solver_resolve_no_unsolved() {
+        let mut solver = Solver {
+            expected: Trinity { a: 1, b: 2, c: 3 },
+            unsolved: vec![Trinity { a: 1, b: 2, c: 3 }, Trinity { a: 1, b: 2, c: 3 }],
+        };
+
+        let report = solver.resolve();
+
+        assert!(solver.unsolved.is_empty());
+        assert_eq!(report.solved.len(), 2);
+        assert_eq!(report.unsolved, 0);
+    }
+
+    #[test]
+    fn test_solver_resolve_all_unsolved() {
+        let mut solver = Solver {
+            expected: Trinity { a: 1, b: 2, c: 3 },
+            unsolved: vec![Trinity { a: 4, b: 5, c: 6 }, Trinity { a: 7, b: 8, c: 9 }],
+        };
+
+        let report = solver.resolve();
+
+        assert_eq!(
+            solver.unsolved,
+            vec![Trinity { a: 4, b: 5, c: 6 }, Trinity { a: 7, b: 8, c: 9 },]
+        );
+        assert!(report.solved.is_empty());
+    }
+
+    #[test]
+    fn test_solver_resolve_cycles() {
+        let mut solver = Solver {
+            expected: Cycle::new([1, 2, 3, 4, 5]),
+            unsolved: vec![
+                Cycle::new([3, 4, 5, 1, 2]),
+                Cycle::new([5, 4, 3, 2, 1]),
+                Cycle::new([5, 1, 2, 3, 4]),
+            ],
+        };
+
+        let report = solver.resolve();
+
+        assert_eq!(
+            report.solved,
+            [
+                Solved {
+                    item: Cycle::new([3, 4, 5, 1, 2]),
+                    rotations: 3,
+                },
+                Solved {
+                    item: Cycle::new([5, 1, 2, 3, 4]),
+                    rotations: 1,
+                },
+            ]
+        );
+        assert_eq!(solver.unsolved, [Cycle::new([5, 4, 3, 2, 1])]);
+    }
+
+    #[test]
+    fn test_solver_resolve_par_matches_sequential() {
+        let unsolved: Vec<_> = (0..10_000_u32)
+            .map(|i| {
+                let mut cycle = Cycle::new([i % 3, i % 5, i % 7, 1, 2, 3]);
+                cycle.rotate_left(i as usize);
+                cycle
+            })
+            .collect();
+        let mut sequential = Solver {
+            expected: Cycle::new([0, 0, 0, 1, 2, 3]),
+            unsolved: unsolved.clone(),
+        };
+        let mut parallel = Solver {
+            expected: Cycle::new([0, 0, 0, 1, 2, 3]),
+            unsolved,
+        };
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap();
+        let report = pool.install(|| parallel.resolve_par());
+
+        assert_eq!(report, sequential.resolve());
+        assert_eq!(parallel.unsolved, sequential.unsolved);
+        assert_eq!(report.solved.len(), 10_000 / 105 + 1);
+        for solved in &report.solved {
+            let mut item = solved.item;
+            item.rotate_left(solved.rotations);
+            assert_eq!(item, parallel.expected);
+        }
+    }
+}
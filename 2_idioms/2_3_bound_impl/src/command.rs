@@ -0,0 +1,318 @@
+use std::time::Duration;
+use std::{error, fmt, thread};
+
+use crate::{Aggregate, AggregateEvent, AggregateId, Entity, EventStore, StoreError};
+
+/// An aggregate validating commands and deciding on the events they result in.
+pub trait CommandHandler: Aggregate {
+    /// An intent to change the aggregate.
+    type Command;
+
+    /// An event produced by handling a command.
+    type Event: AggregateEvent<Self>;
+
+    /// The reason of rejecting a command.
+    type Error;
+
+    /// Decides on the events resulting from the command, without applying them.
+    ///
+    /// Returns no events if the command changes nothing.
+    fn handle(&self, cmd: &Self::Command) -> Result<Vec<Self::Event>, Self::Error>;
+}
+
+/// An error of executing a command.
+#[derive(Debug)]
+pub enum ExecuteError<E> {
+    /// The aggregate rejected the command.
+    Rejected(E),
+    /// The event store failed, or kept conflicting after all the retries.
+    Store(StoreError),
+}
+
+impl<E: fmt::Display> fmt::Display for ExecuteError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rejected(e) => write!(f, "command rejected: {e}"),
+            Self::Store(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl<E> error::Error for ExecuteError<E>
+where
+    E: error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Rejected(e) => Some(e),
+            Self::Store(e) => Some(e),
+        }
+    }
+}
+
+impl<E> From<StoreError> for ExecuteError<E> {
+    fn from(e: StoreError) -> Self {
+        Self::Store(e)
+    }
+}
+
+/// Executor of commands against the aggregates persisted in an [`EventStore`].
+///
+/// A command is handled by the up-to-date aggregate, and its events are committed only if no
+/// other events were appended in the meantime. Otherwise, the aggregate catches up after a
+/// backoff, and the command is handled again.
+///
+/// Aggregates are loaded and committed through the [`EventStore`], so executing commands
+/// against a [`SnapshotRepository`] loads them from snapshots and takes new ones.
+///
+/// [`SnapshotRepository`]: crate::SnapshotRepository
+#[derive(Debug)]
+pub struct CommandExecutor<S> {
+    store: S,
+    retries: usize,
+    backoff: Duration,
+}
+
+impl<S> CommandExecutor<S> {
+    /// The number of retries on conflicts by default.
+    pub const DEFAULT_RETRIES: usize = 3;
+
+    /// The delay before the first retry by default.
+    pub const DEFAULT_BACKOFF: Duration = Duration::from_millis(1);
+
+    /// The longest delay between retries, however many there were.
+    pub const MAX_BACKOFF: Duration = Duration::from_millis(100);
+
+    pub fn new(store: S) -> Self {
+        Self {
+            store,
+            retries: Self::DEFAULT_RETRIES,
+            backoff: Self::DEFAULT_BACKOFF,
+        }
+    }
+
+    /// Sets the number of times a command is retried on conflicts before giving up.
+    pub fn with_retries(mut self, retries: usize) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// Sets the delay before the first retry, which doubles on every next one.
+    pub fn with_backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// The underlying event store.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Executes the command against the aggregate identified by `id`, returning the aggregate
+    /// with the resulting events applied.
+    pub fn execute<A, I>(
+        &self,
+        id: I,
+        cmd: &A::Command,
+    ) -> Result<Entity<I, A>, ExecuteError<A::Error>>
+    where
+        A: CommandHandler,
+        I: AggregateId<A>,
+        S: EventStore<A, A::Event>,
+    {
+        let mut entity = self.store.load(id)?;
+        let mut retries = self.retries;
+        let mut backoff = self.backoff;
+        loop {
+            let events = entity
+                .aggregate()
+                .state()
+                .handle(cmd)
+                .map_err(ExecuteError::Rejected)?;
+            match self.store.commit(&mut entity, events) {
+                Ok(_) => return Ok(entity),
+                Err(StoreError::Conflict { .. }) if retries > 0 => retries -= 1,
+                Err(e) => return Err(e.into()),
+            }
+
+            thread::sleep(backoff);
+            backoff = backoff.saturating_mul(2).min(Self::MAX_BACKOFF);
+            let stored = self
+                .store
+                .read(entity.identifier_str(), entity.aggregate().version())?;
+            let events = stored.into_iter().map(|(_, event)| event);
+            entity.aggregate_mut().apply_events(events);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::{
+        FileEventStore, FileSnapshotStore, MyAggregate, MyAggregateId, MyCommand, MyError, MyEvent,
+        SnapshotPolicy, SnapshotRepository, SnapshotStore, Version,
+    };
+
+    fn executor() -> (tempfile::TempDir, CommandExecutor<FileEventStore>) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileEventStore::open(dir.path()).unwrap();
+        (dir, CommandExecutor::new(store))
+    }
+
+    fn execute(
+        executor: &CommandExecutor<FileEventStore>,
+        cmd: MyCommand,
+    ) -> Result<Entity<MyAggregateId, MyAggregate>, ExecuteError<MyError>> {
+        executor.execute(MyAggregateId("a".to_owned()), &cmd)
+    }
+
+    #[test]
+    fn test_handle_forbids_negative_count() {
+        let aggregate = MyAggregate { count: 5 };
+
+        assert_eq!(
+            aggregate.handle(&MyCommand::Decrement(5)),
+            Ok(vec![MyEvent::Decrement(5)])
+        );
+        assert_eq!(
+            aggregate.handle(&MyCommand::Increment(-2)),
+            Ok(vec![MyEvent::increment(-2)])
+        );
+        assert_eq!(
+            aggregate.handle(&MyCommand::Decrement(6)),
+            Err(MyError::NegativeCount(-1))
+        );
+        assert_eq!(
+            aggregate.handle(&MyCommand::Increment(-7)),
+            Err(MyError::NegativeCount(-2))
+        );
+        assert_eq!(
+            aggregate.handle(&MyCommand::Increment(i64::MAX)),
+            Err(MyError::Overflow)
+        );
+    }
+
+    #[test]
+    fn test_execute_appends_events() {
+        let (_dir, executor) = executor();
+
+        let entity = execute(&executor, MyCommand::Increment(3)).unwrap();
+        assert_eq!(entity.aggregate().state().count, 3);
+        let entity = execute(&executor, MyCommand::Decrement(1)).unwrap();
+        assert_eq!(entity.aggregate().state().count, 2);
+        assert_eq!(entity.aggregate().version(), Version::new(2));
+
+        let stored =
+            EventStore::<MyAggregate, MyEvent>::load(executor.store(), entity.id().clone());
+        assert_eq!(stored.unwrap().aggregate().state().count, 2);
+    }
+
+    #[test]
+    fn test_execute_takes_snapshots() {
+        let dir = tempfile::tempdir().unwrap();
+        let events = FileEventStore::open(dir.path().join("events")).unwrap();
+        let snapshots = FileSnapshotStore::open(dir.path().join("snapshots")).unwrap();
+        let repo = SnapshotRepository::<MyAggregate, MyEvent, _, _>::new(
+            events,
+            snapshots,
+            SnapshotPolicy::EveryEvents(2),
+        );
+        let executor = CommandExecutor::new(repo);
+
+        for _ in 0..3 {
+            executor
+                .execute(MyAggregateId("a".to_owned()), &MyCommand::Increment(1))
+                .unwrap();
+        }
+        let snapshots = executor.store().snapshots();
+        let snapshot = SnapshotStore::<MyAggregate>::load(snapshots, "a")
+            .unwrap()
+            .unwrap();
+        assert_eq!(snapshot.version, Version::new(2));
+        let entity = executor
+            .execute(MyAggregateId("a".to_owned()), &MyCommand::Increment(1))
+            .unwrap();
+        assert_eq!(entity.aggregate().snapshot_version(), Some(Version::new(4)));
+        assert_eq!(entity.aggregate().state().count, 4);
+    }
+
+    #[test]
+    fn test_rejected_command_appends_nothing() {
+        let (_dir, executor) = executor();
+        execute(&executor, MyCommand::Increment(1)).unwrap();
+
+        let res = execute(&executor, MyCommand::Decrement(2));
+        assert!(matches!(
+            res,
+            Err(ExecuteError::Rejected(MyError::NegativeCount(-1)))
+        ));
+        let entity = execute(&executor, MyCommand::Increment(0)).unwrap();
+        assert_eq!(entity.aggregate().version(), Version::new(2));
+        assert_eq!(entity.aggregate().state().count, 1);
+    }
+
+    #[test]
+    fn test_concurrent_commands_are_retried() {
+        let (_dir, executor) = executor();
+        let executor = executor.with_retries(16);
+        execute(&executor, MyCommand::Increment(10)).unwrap();
+
+        // Every conflict means another command succeeded, so 16 retries are always enough.
+        let results: Vec<_> = thread::scope(|s| {
+            let handles: Vec<_> = (0..16)
+                .map(|_| s.spawn(|| execute(&executor, MyCommand::Decrement(1))))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        let executed = results.iter().filter(|res| res.is_ok()).count();
+        let rejected = results
+            .iter()
+            .filter(|res| matches!(res, Err(ExecuteError::Rejected(_))))
+            .count();
+        assert_eq!((executed, rejected), (10, 6));
+        let entity = execute(&executor, MyCommand::Increment(0)).unwrap();
+        assert_eq!(entity.aggregate().state().count, 0);
+    }
+
+    #[test]
+    fn test_gives_up_after_retries() {
+        struct Conflicting(FileEventStore);
+
+        impl EventStore<MyAggregate, MyEvent> for Conflicting {
+            fn append(
+                &self,
+                _: &str,
+                expected: Version,
+                _: &[MyEvent],
+            ) -> Result<Version, StoreError> {
+                let actual = Version::new(100);
+                Err(StoreError::Conflict { expected, actual })
+            }
+
+            fn read(
+                &self,
+                id: &str,
+                after: Version,
+            ) -> Result<Vec<(crate::EventNumber, MyEvent)>, StoreError> {
+                EventStore::<MyAggregate, _>::read(&self.0, id, after)
+            }
+
+            fn ids(&self) -> Result<Vec<String>, StoreError> {
+                EventStore::<MyAggregate, MyEvent>::ids(&self.0)
+            }
+        }
+
+        let (_dir, executor) = executor();
+        let executor = CommandExecutor::new(Conflicting(executor.store)).with_retries(2);
+        let res: Result<Entity<_, MyAggregate>, _> =
+            executor.execute(MyAggregateId("a".to_owned()), &MyCommand::Increment(1));
+        assert!(matches!(
+            res,
+            Err(ExecuteError::Store(StoreError::Conflict { .. }))
+        ));
+    }
+}
@@ -0,0 +1,627 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write as _};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::{error, fmt, str};
+
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    Aggregate, AggregateEvent, AggregateId, Entity, EventNumber, HydratedAggregate, Upcasters,
+    Version, VersionOverflow,
+};
+
+/// An error of an event store operation.
+#[derive(Debug)]
+pub enum StoreError {
+    /// The stream isn't at the expected version, as it was appended to concurrently.
+    Conflict { expected: Version, actual: Version },
+    /// Accessing the underlying storage failed.
+    Io(io::Error),
+    /// An event couldn't be serialized or deserialized.
+    Serde(serde_json::Error),
+    /// The stream can't have more events.
+    Overflow(VersionOverflow),
+    /// The stored events are malformed.
+    Corrupted(String),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Conflict { expected, actual } => write!(
+                f,
+                "stream is at version {actual} instead of the expected {expected}",
+            ),
+            Self::Io(e) => write!(f, "event storage failed: {e}"),
+            Self::Serde(e) => write!(f, "malformed event: {e}"),
+            Self::Overflow(e) => write!(f, "stream is full: {e}"),
+            Self::Corrupted(reason) => write!(f, "corrupted event stream: {reason}"),
+        }
+    }
+}
+
+impl error::Error for StoreError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Serde(e) => Some(e),
+            Self::Overflow(e) => Some(e),
+            Self::Conflict { .. } | Self::Corrupted(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for StoreError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<VersionOverflow> for StoreError {
+    fn from(e: VersionOverflow) -> Self {
+        Self::Overflow(e)
+    }
+}
+
+impl From<serde_json::Error> for StoreError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Serde(e)
+    }
+}
+
+/// A persistent, append-only stream of events for each aggregate.
+pub trait EventStore<A, E>
+where
+    A: Aggregate,
+    E: AggregateEvent<A>,
+{
+    /// Appends the events to the stream of the aggregate identified by `id`, returning its new
+    /// version.
+    ///
+    /// Fails with [`StoreError::Conflict`] unless the stream is at the `expected` version, so
+    /// concurrent writers never overwrite each other's events.
+    fn append(&self, id: &str, expected: Version, events: &[E]) -> Result<Version, StoreError>;
+
+    /// Reads the events of the aggregate identified by `id`, which follow the `after` version.
+    fn read(&self, id: &str, after: Version) -> Result<Vec<(EventNumber, E)>, StoreError>;
+
+    /// Lists the ids of the aggregates having events stored, in ascending order.
+    fn ids(&self) -> Result<Vec<String>, StoreError>;
+
+    /// Loads the aggregate identified by `id` by replaying all its events.
+    fn load<I>(&self, id: I) -> Result<Entity<I, A>, StoreError>
+    where
+        I: AggregateId<A>,
+        Self: Sized,
+    {
+        let events = self.read(id.as_str(), Version::Initial)?;
+        let mut aggregate = HydratedAggregate::default();
+        aggregate.apply_events(events.into_iter().map(|(_, event)| event));
+        Ok(Entity::new(id, aggregate))
+    }
+
+    /// Appends the `events` to the stream of the `entity` and applies them to it, returning
+    /// its new version.
+    ///
+    /// Fails with [`StoreError::Conflict`] if the `entity` is stale, leaving it untouched.
+    fn commit<I>(&self, entity: &mut Entity<I, A>, events: Vec<E>) -> Result<Version, StoreError>
+    where
+        I: AggregateId<A>,
+        Self: Sized,
+    {
+        let version = self.append(
+            entity.identifier_str(),
+            entity.aggregate().version(),
+            &events,
+        )?;
+        entity.aggregate_mut().apply_events(events);
+        Ok(version)
+    }
+}
+
+/// An [`EventStore`] keeping each stream in a JSON Lines file.
+///
+/// Streams are stored as `<dir>/<aggregate type>/<id>.jsonl`. Operations on a stream are
+/// serialized within the process only, so the directory must not be shared by multiple
+/// processes.
+///
+/// Events stored with outdated schemas are upcast to the current ones when read.
+#[derive(Debug)]
+pub struct FileEventStore {
+    dir: PathBuf,
+    upcasters: Upcasters,
+    /// Versions of the streams being accessed, by their paths, unless they need a scan.
+    ///
+    /// The lock of a stream is held for the whole operation on it, so its version can't
+    /// change in between, while operations on other streams proceed. A stream is evicted
+    /// once no operation on it is left, so the map doesn't grow with every id ever used.
+    versions: Mutex<HashMap<PathBuf, Arc<Mutex<Option<Version>>>>>,
+}
+
+/// A stored event, as a line of a stream file.
+#[derive(Serialize, Deserialize)]
+struct Record<'a, P> {
+    number: u64,
+    #[serde(rename = "type")]
+    event_type: Cow<'a, str>,
+    /// Missing in the events stored before schemas were versioned.
+    #[serde(default = "first_schema")]
+    schema: u32,
+    payload: P,
+}
+
+fn first_schema() -> u32 {
+    1
+}
+
+/// The number of a stored event, decoded without the rest of its line.
+#[derive(Deserialize)]
+struct Header {
+    number: u64,
+}
+
+impl FileEventStore {
+    /// Opens the store in the `dir`, creating it if it doesn't exist.
+    pub fn open(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            upcasters: Upcasters::new(),
+            versions: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Sets the upcasters of the events stored with outdated schemas.
+    pub fn with_upcasters(mut self, upcasters: Upcasters) -> Self {
+        self.upcasters = upcasters;
+        self
+    }
+
+    /// The directory the streams are stored in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn stream_path<A: Aggregate>(&self, id: &str) -> PathBuf {
+        self.dir
+            .join(A::aggregate_type())
+            .join(format!("{}.jsonl", escape(id)))
+    }
+
+    /// Runs the `op` on the stream at `path` under its lock, with its version.
+    fn with_stream<T>(&self, path: &Path, op: impl FnOnce(&mut Option<Version>) -> T) -> T {
+        let lock = Arc::clone(
+            self.versions
+                .lock()
+                .unwrap()
+                .entry(path.to_owned())
+                .or_default(),
+        );
+        let res = op(&mut lock.lock().unwrap());
+        let mut versions = self.versions.lock().unwrap();
+        // The map and this operation are the only holders, so no other one is waiting.
+        if Arc::strong_count(&lock) == 2 {
+            versions.remove(path);
+        }
+        res
+    }
+}
+
+impl<A, E> EventStore<A, E> for FileEventStore
+where
+    A: Aggregate,
+    E: AggregateEvent<A> + Serialize + DeserializeOwned,
+{
+    fn append(&self, id: &str, expected: Version, events: &[E]) -> Result<Version, StoreError> {
+        let path = self.stream_path::<A>(id);
+        self.with_stream(&path, |cached| {
+            let actual = match *cached {
+                Some(version) => version,
+                // Only the version is needed, so no record is kept.
+                None => scan::<IgnoredAny>(&path, Version::Number(EventNumber::MAX_VALUE))?.0,
+            };
+            *cached = Some(actual);
+            if actual != expected {
+                return Err(StoreError::Conflict { expected, actual });
+            }
+            if events.is_empty() {
+                return Ok(actual);
+            }
+
+            let mut lines = Vec::new();
+            let mut version = actual;
+            for event in events {
+                version = version.next()?;
+                let record = Record {
+                    number: version.as_u64(),
+                    event_type: Cow::Borrowed(event.event_type()),
+                    schema: event.schema_version(),
+                    payload: event,
+                };
+                serde_json::to_writer(&mut lines, &record)?;
+                lines.push(b'\n');
+            }
+            fs::create_dir_all(path.parent().expect("stream path has a parent"))?;
+            let mut file = OpenOptions::new().create(true).append(true).open(&path)?;
+            let len = file.metadata()?.len();
+            let res = file.write_all(&lines).and_then(|()| file.sync_data());
+            if let Err(e) = res {
+                // Some of the events could be written, so they're truncated away to keep the
+                // batch all-or-nothing. Failing that, the stream has to be scanned again.
+                if file.set_len(len).and_then(|()| file.sync_data()).is_err() {
+                    *cached = None;
+                }
+                return Err(e.into());
+            }
+            *cached = Some(version);
+            Ok(version)
+        })
+    }
+
+    fn read(&self, id: &str, after: Version) -> Result<Vec<(EventNumber, E)>, StoreError> {
+        let path = self.stream_path::<A>(id);
+        let (_, records) = self.with_stream(&path, |cached| {
+            let res = scan::<Value>(&path, after);
+            *cached = res.as_ref().ok().map(|&(version, _)| version);
+            res
+        })?;
+        records
+            .into_iter()
+            .map(|record| {
+                let number = EventNumber::new(record.number).expect("scanned numbers start at 1");
+                let (schema, payload) = self
+                    .upcasters
+                    .upcast(&record.event_type, record.schema, record.payload)
+                    .map_err(|reason| StoreError::Corrupted(format!("event {number}: {reason}")))?;
+                let event: E = serde_json::from_value(payload)?;
+                if event.schema_version() != schema {
+                    return Err(StoreError::Corrupted(format!(
+                        "no upcaster of {} event {number:?} from schema v{schema}",
+                        record.event_type,
+                    )));
+                }
+                Ok((number, event))
+            })
+            .collect()
+    }
+
+    fn ids(&self) -> Result<Vec<String>, StoreError> {
+        let entries = match fs::read_dir(self.dir.join(A::aggregate_type())) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut ids = Vec::new();
+        for entry in entries {
+            let name = entry?.file_name();
+            let Some(escaped) = name.to_str().and_then(|name| name.strip_suffix(".jsonl")) else {
+                continue;
+            };
+            let id = unescape(escaped).ok_or_else(|| {
+                StoreError::Corrupted(format!("malformed stream file name: {escaped}"))
+            })?;
+            ids.push(id);
+        }
+        ids.sort_unstable();
+        Ok(ids)
+    }
+}
+
+/// Reads the version of the stream at `path` and its records following the `after` version,
+/// truncating its torn tail, if any.
+///
+/// Payloads of the preceding records are skipped without being decoded. A missing file is an
+/// empty stream.
+fn scan<P: DeserializeOwned>(
+    path: &Path,
+    after: Version,
+) -> Result<(Version, Vec<Record<'static, P>>), StoreError> {
+    let file = match OpenOptions::new().read(true).write(true).open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok((Version::Initial, Vec::new())),
+        Err(e) => return Err(e.into()),
+    };
+    let mut reader = BufReader::new(&file);
+    let mut records: Vec<Record<P>> = Vec::new();
+    let mut line = Vec::new();
+    let mut valid_len = 0;
+    let mut version = 0;
+    loop {
+        line.clear();
+        let len = reader.read_until(b'\n', &mut line)?;
+        if len == 0 {
+            break;
+        }
+        if line.last() != Some(&b'\n') {
+            // Left by an interrupted append, which was never acknowledged.
+            file.set_len(valid_len)?;
+            break;
+        }
+        let Header { number } = serde_json::from_slice(&line)?;
+        if number != version + 1 {
+            return Err(StoreError::Corrupted(format!(
+                "{}: expected event {}, found {number}",
+                path.display(),
+                version + 1,
+            )));
+        }
+        if number > after.as_u64() {
+            records.push(serde_json::from_slice(&line)?);
+        }
+        version = number;
+        valid_len += len as u64;
+    }
+    Ok((Version::new(version), records))
+}
+
+/// Escapes the `id` to be usable as a file name, keeping distinct ids distinct.
+///
+/// Only lowercase letters, digits, `-` and `_` are kept as is, so ids differing in case
+/// don't collide on case-insensitive filesystems either.
+pub(crate) fn escape(id: &str) -> String {
+    let mut escaped = String::with_capacity(id.len());
+    for byte in id.bytes() {
+        if byte.is_ascii_lowercase() || byte.is_ascii_digit() || byte == b'-' || byte == b'_' {
+            escaped.push(char::from(byte));
+        } else {
+            write!(escaped, "%{byte:02X}").unwrap();
+        }
+    }
+    escaped
+}
+
+/// Reverts the [`escape`] of an id, unless the `escaped` one is malformed.
+fn unescape(escaped: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(escaped.len());
+    let mut rest = escaped.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{slice, thread};
+
+    use super::*;
+    use crate::{MyAggregate, MyAggregateId, MyEvent};
+
+    fn store() -> (tempfile::TempDir, FileEventStore) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileEventStore::open(dir.path()).unwrap();
+        (dir, store)
+    }
+
+    // Pins the aggregate type, which can't be inferred from `MyEvent` alone.
+    fn append(
+        store: &FileEventStore,
+        id: &str,
+        expected: Version,
+        events: &[MyEvent],
+    ) -> Result<Version, StoreError> {
+        EventStore::<MyAggregate, _>::append(store, id, expected, events)
+    }
+
+    fn load(store: &FileEventStore, id: &str) -> Entity<MyAggregateId, MyAggregate> {
+        EventStore::<_, MyEvent>::load(store, MyAggregateId(id.to_owned())).unwrap()
+    }
+
+    #[test]
+    fn test_load_replays_events() {
+        let (_dir, store) = store();
+        let version = append(
+            &store,
+            "a",
+            Version::Initial,
+            &[MyEvent::increment(10), MyEvent::Decrement(3)],
+        )
+        .unwrap();
+        assert_eq!(version, Version::new(2));
+        append(&store, "a", version, &[MyEvent::increment(1)]).unwrap();
+        append(&store, "b", Version::Initial, &[MyEvent::increment(100)]).unwrap();
+
+        let entity = load(&store, "a");
+        assert_eq!(entity.aggregate().state().count, 8);
+        assert_eq!(entity.aggregate().version(), Version::new(3));
+
+        // Reopening loses nothing.
+        let store = FileEventStore::open(store.dir()).unwrap();
+        let entity = load(&store, "b");
+        assert_eq!(entity.aggregate().state().count, 100);
+        let entity = load(&store, "missing");
+        assert_eq!(entity.aggregate().version(), Version::Initial);
+    }
+
+    #[test]
+    fn test_read_after_version() {
+        let (_dir, store) = store();
+        let events = [1, 2, 3].map(MyEvent::increment);
+        append(&store, "a", Version::Initial, &events).unwrap();
+
+        let read = EventStore::<MyAggregate, MyEvent>::read(&store, "a", Version::new(1)).unwrap();
+        let numbers: Vec<_> = read.iter().map(|(number, _)| number.get()).collect();
+        assert_eq!(numbers, [2, 3]);
+        assert_eq!(read[1].1, MyEvent::increment(3));
+    }
+
+    #[test]
+    fn test_read_skips_preceding_payloads() {
+        let (_dir, store) = store();
+        let path = store.stream_path::<MyAggregate>("a");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let line = r#"{"number":1,"type":"Increment","payload":"undecodable"}"#;
+        fs::write(&path, format!("{line}\n")).unwrap();
+        append(&store, "a", Version::new(1), &[MyEvent::increment(2)]).unwrap();
+
+        let read = EventStore::<MyAggregate, MyEvent>::read(&store, "a", Version::Initial);
+        assert!(matches!(read, Err(StoreError::Serde(_))));
+        let read = EventStore::<MyAggregate, MyEvent>::read(&store, "a", Version::new(1)).unwrap();
+        assert_eq!(
+            read,
+            [(EventNumber::new(2).unwrap(), MyEvent::increment(2))]
+        );
+    }
+
+    #[test]
+    fn test_append_conflicts() {
+        let (_dir, store) = store();
+        append(&store, "a", Version::Initial, &[MyEvent::increment(1)]).unwrap();
+
+        let err = append(&store, "a", Version::Initial, &[MyEvent::increment(2)]).unwrap_err();
+        assert!(matches!(
+            err,
+            StoreError::Conflict {
+                expected: Version::Initial,
+                actual,
+            } if actual == Version::new(1)
+        ));
+        let err = append(&store, "b", Version::new(1), &[]).unwrap_err();
+        assert!(matches!(err, StoreError::Conflict { .. }));
+    }
+
+    #[test]
+    fn test_concurrent_appends() {
+        let (_dir, store) = store();
+
+        let results: Vec<_> = thread::scope(|s| {
+            let handles: Vec<_> = (0..8)
+                .map(|i| {
+                    let store = &store;
+                    s.spawn(move || append(store, "a", Version::Initial, &[MyEvent::increment(i)]))
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        assert_eq!(results.iter().filter(|res| res.is_ok()).count(), 1);
+        let entity = load(&store, "a");
+        assert_eq!(entity.aggregate().version(), Version::new(1));
+        assert!(store.versions.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_idle_streams_are_evicted() {
+        let (_dir, store) = store();
+        for id in ["a", "b", "c"] {
+            append(&store, id, Version::Initial, &[MyEvent::increment(1)]).unwrap();
+            load(&store, id);
+        }
+        assert!(store.versions.lock().unwrap().is_empty());
+
+        // The evicted versions are scanned again.
+        assert!(matches!(
+            append(&store, "a", Version::Initial, &[MyEvent::increment(1)]),
+            Err(StoreError::Conflict { .. }),
+        ));
+        append(&store, "a", Version::new(1), &[MyEvent::increment(1)]).unwrap();
+    }
+
+    #[test]
+    fn test_ids_are_listed() {
+        let (_dir, store) = store();
+        assert_eq!(
+            EventStore::<MyAggregate, MyEvent>::ids(&store).unwrap(),
+            [""; 0]
+        );
+
+        for id in ["b", "a/b", "ä.1", "", "B"] {
+            append(&store, id, Version::Initial, &[MyEvent::increment(1)]).unwrap();
+        }
+        let ids = EventStore::<MyAggregate, MyEvent>::ids(&store).unwrap();
+        assert_eq!(ids, ["", "B", "a/b", "b", "ä.1"]);
+        assert_eq!(escape("aB-_9"), "a%42-_9");
+    }
+
+    #[test]
+    fn test_outdated_events_are_upcast() {
+        let (_dir, store) = store();
+        let path = store.stream_path::<MyAggregate>("a");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(
+            &path,
+            concat!(
+                r#"{"number":1,"type":"Increment","payload":{"Increment":5}}"#,
+                "\n",
+                r#"{"number":2,"type":"Decrement","payload":{"Decrement":2}}"#,
+                "\n",
+            ),
+        )
+        .unwrap();
+
+        let read = EventStore::<MyAggregate, MyEvent>::read(&store, "a", Version::Initial);
+        assert!(matches!(read, Err(StoreError::Serde(_))));
+
+        let store = FileEventStore::open(store.dir())
+            .unwrap()
+            .with_upcasters(MyEvent::upcasters());
+        let increment = MyEvent::Increment {
+            by: 1,
+            reason: Some("current".to_owned()),
+        };
+        append(&store, "a", Version::new(2), slice::from_ref(&increment)).unwrap();
+        let read = EventStore::<MyAggregate, MyEvent>::read(&store, "a", Version::Initial);
+        let events: Vec<_> = read.unwrap().into_iter().map(|(_, event)| event).collect();
+        assert_eq!(
+            events,
+            [MyEvent::increment(5), MyEvent::Decrement(2), increment],
+        );
+        assert_eq!(load(&store, "a").aggregate().state().count, 4);
+    }
+
+    #[test]
+    fn test_malformed_outdated_event_is_corrupted() {
+        let (_dir, store) = store();
+        let store = store.with_upcasters(MyEvent::upcasters());
+        let path = store.stream_path::<MyAggregate>("a");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let line = r#"{"number":1,"type":"Increment","payload":5}"#;
+        fs::write(&path, format!("{line}\n")).unwrap();
+
+        let read = EventStore::<MyAggregate, MyEvent>::read(&store, "a", Version::Initial);
+        assert!(matches!(read, Err(StoreError::Corrupted(_))));
+    }
+
+    #[test]
+    fn test_missing_upcaster_is_detected() {
+        let (_dir, store) = store();
+        let path = store.stream_path::<MyAggregate>("a");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        // Shaped as the current version, but claiming an outdated one.
+        let line = r#"{"number":1,"type":"Increment","schema":1,"payload":{"Increment":{"by":1,"reason":null}}}"#;
+        fs::write(&path, format!("{line}\n")).unwrap();
+
+        let read = EventStore::<MyAggregate, MyEvent>::read(&store, "a", Version::Initial);
+        assert!(matches!(read, Err(StoreError::Corrupted(_))));
+    }
+
+    #[test]
+    fn test_torn_tail_is_truncated() {
+        let (_dir, store) = store();
+        append(&store, "a/b", Version::Initial, &[MyEvent::increment(1)]).unwrap();
+
+        let path = store.stream_path::<MyAggregate>("a/b");
+        assert!(path.ends_with("MyAggregate/a%2Fb.jsonl"));
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"number":2,"type":"Incr"#).unwrap();
+
+        let store = FileEventStore::open(store.dir()).unwrap();
+        append(&store, "a/b", Version::new(1), &[MyEvent::increment(2)]).unwrap();
+        let entity = load(&store, "a/b");
+        assert_eq!(entity.aggregate().state().count, 3);
+    }
+}
@@ -0,0 +1,674 @@
+use std::{
+    borrow::{Borrow, BorrowMut},
+    collections::BTreeMap,
+    error, fmt,
+    num::NonZeroU64,
+    ops::Sub,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+pub mod command;
+pub mod event_store;
+pub mod projection;
+pub mod snapshot;
+pub mod upcast;
+
+pub use self::{
+    command::{CommandExecutor, CommandHandler, ExecuteError},
+    event_store::{EventStore, FileEventStore, StoreError},
+    projection::{Checkpoints, Projection, Projector},
+    snapshot::{FileSnapshotStore, Snapshot, SnapshotPolicy, SnapshotRepository, SnapshotStore},
+    upcast::Upcasters,
+};
+
+/// A projected state built from a series of events.
+pub trait Aggregate: Default {
+    /// A static string representing the type of the aggregate.
+    ///
+    /// Note: This should effectively be a constant value, and should never change.
+    fn aggregate_type() -> &'static str;
+
+    /// Consumes the event, applying its effects to the aggregate.
+    fn apply<E>(&mut self, event: E)
+    where
+        E: AggregateEvent<Self>,
+    {
+        event.apply_to(self);
+    }
+}
+
+/// An identifier for an aggregate.
+pub trait AggregateId<A> {
+    /// Gets the stringified aggregate identifier.
+    fn as_str(&self) -> &str;
+}
+
+/// A thing that happened.
+pub trait Event {
+    /// A static description of the event.
+    fn event_type(&self) -> &'static str;
+
+    /// The version of the schema of the event, starting at 1.
+    ///
+    /// Note: This should be incremented on every incompatible change of the event's shape, along
+    /// with registering an upcaster from the previous version.
+    fn schema_version(&self) -> u32 {
+        1
+    }
+}
+
+/// An event that can be applied to an aggregate.
+pub trait AggregateEvent<A>: Event {
+    /// Consumes the event, applying its effects to the aggregate.
+    fn apply_to(self, aggregate: &mut A);
+}
+
+/// An error of a version or an event number exceeding its maximum value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VersionOverflow;
+
+impl fmt::Display for VersionOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("event number overflowed")
+    }
+}
+
+impl error::Error for VersionOverflow {}
+
+/// Represents an event sequence number, starting at 1
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct EventNumber(NonZeroU64);
+
+impl EventNumber {
+    pub const MIN_VALUE: EventNumber = EventNumber(NonZeroU64::MIN);
+    pub const MAX_VALUE: EventNumber = EventNumber(NonZeroU64::MAX);
+
+    /// Creates an event number, unless the `number` is zero.
+    #[inline]
+    pub fn new(number: u64) -> Option<Self> {
+        NonZeroU64::new(number).map(EventNumber)
+    }
+
+    /// The event number as a plain integer.
+    #[inline]
+    pub fn get(self) -> u64 {
+        self.0.get()
+    }
+
+    /// The next event number, unless this one is the maximum.
+    #[inline]
+    pub fn next(self) -> Result<Self, VersionOverflow> {
+        self.0
+            .checked_add(1)
+            .map(EventNumber)
+            .ok_or(VersionOverflow)
+    }
+
+    /// Increments the event number to the next value, leaving it unchanged on overflow.
+    #[inline]
+    pub fn incr(&mut self) -> Result<(), VersionOverflow> {
+        *self = self.next()?;
+        Ok(())
+    }
+
+    /// Increments the event number to the next value, unless it's the maximum already.
+    #[inline]
+    pub fn saturating_incr(&mut self) {
+        self.0 = self.0.saturating_add(1);
+    }
+}
+
+impl fmt::Display for EventNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+/// An aggregate version.
+///
+/// Serialized as the number of applied events, which is `0` for the `Initial` version.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(from = "u64", into = "u64")]
+pub enum Version {
+    /// The version of an aggregate that has not had any events applied to it.
+    Initial,
+    /// The version of the last event applied to the aggregate.
+    Number(EventNumber),
+}
+
+impl Default for Version {
+    #[inline]
+    fn default() -> Self {
+        Version::Initial
+    }
+}
+
+impl Version {
+    #[inline]
+    pub fn new(number: u64) -> Self {
+        EventNumber::new(number)
+            .map(Version::Number)
+            .unwrap_or(Version::Initial)
+    }
+
+    /// The number of events applied to reach this version.
+    #[inline]
+    pub fn as_u64(self) -> u64 {
+        match self {
+            Version::Initial => 0,
+            Version::Number(en) => en.get(),
+        }
+    }
+
+    /// The version after applying one more event, unless this one is the maximum.
+    #[inline]
+    pub fn next(self) -> Result<Self, VersionOverflow> {
+        match self {
+            Version::Initial => Ok(Version::Number(EventNumber::MIN_VALUE)),
+            Version::Number(en) => en.next().map(Version::Number),
+        }
+    }
+
+    /// Increments the version to the next value, leaving it unchanged on overflow.
+    #[inline]
+    pub fn incr(&mut self) -> Result<(), VersionOverflow> {
+        *self = self.next()?;
+        Ok(())
+    }
+
+    /// Increments the version to the next value, unless it's the maximum already.
+    #[inline]
+    pub fn saturating_incr(&mut self) {
+        match *self {
+            Version::Initial => *self = Version::Number(EventNumber::MIN_VALUE),
+            Version::Number(ref mut en) => en.saturating_incr(),
+        }
+    }
+
+    /// The number of events between this version and the `older` one, unless it's newer.
+    #[inline]
+    pub fn checked_sub(self, older: Version) -> Option<u64> {
+        self.as_u64().checked_sub(older.as_u64())
+    }
+}
+
+impl From<u64> for Version {
+    #[inline]
+    fn from(number: u64) -> Self {
+        Version::new(number)
+    }
+}
+
+impl From<Version> for u64 {
+    #[inline]
+    fn from(version: Version) -> Self {
+        version.as_u64()
+    }
+}
+
+/// The number of events between two versions.
+///
+/// # Panics
+///
+/// If the subtrahend is newer. Use `Version::checked_sub` to handle that case.
+impl Sub for Version {
+    type Output = u64;
+
+    #[inline]
+    fn sub(self, older: Version) -> u64 {
+        self.checked_sub(older)
+            .unwrap_or_else(|| panic!("version {older} is newer than {self}"))
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.as_u64(), f)
+    }
+}
+
+#[derive(Debug)]
+pub struct HydratedAggregate<A> {
+    version: Version,
+    snapshot_version: Option<Version>,
+    state: A,
+}
+
+impl<A: Default> Default for HydratedAggregate<A> {
+    fn default() -> Self {
+        HydratedAggregate {
+            version: Version::Initial,
+            snapshot_version: None,
+            state: A::default(),
+        }
+    }
+}
+
+impl<A> HydratedAggregate<A> {
+    /// Restores the aggregate from its `state` snapshotted at the `version`.
+    pub fn from_snapshot(version: Version, state: A) -> Self {
+        HydratedAggregate {
+            version,
+            snapshot_version: Some(version),
+            state,
+        }
+    }
+
+    /// The current version of the aggregate.
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// The version of the snapshot from which the aggregate was loaded.
+    pub fn snapshot_version(&self) -> Option<Version> {
+        self.snapshot_version
+    }
+
+    /// Updates the snapshot version. Generally used to indicate that a snapshot was taken.
+    pub fn set_snapshot_version(&mut self, new_snapshot_version: Version) {
+        self.snapshot_version = Some(new_snapshot_version);
+    }
+
+    /// The actual aggregate.
+    pub fn state(&self) -> &A {
+        &self.state
+    }
+
+    /// Applies a sequence of events to the internal aggregate.
+    pub fn apply_events<E, I>(&mut self, events: I)
+    where
+        A: Aggregate,
+        E: AggregateEvent<A>,
+        I: IntoIterator<Item = E>,
+    {
+        for event in events {
+            self.apply(event);
+        }
+    }
+
+    /// Applies a single event to the aggregate, keeping track of the new aggregate version.
+    ///
+    /// The version saturates at its maximum, which no event stream can reach in practice.
+    pub fn apply<E>(&mut self, event: E)
+    where
+        A: Aggregate,
+        E: AggregateEvent<A>,
+    {
+        self.state.apply(event);
+        self.version.saturating_incr();
+    }
+}
+
+impl<A> AsRef<A> for HydratedAggregate<A> {
+    fn as_ref(&self) -> &A {
+        &self.state
+    }
+}
+
+impl<A> Borrow<A> for HydratedAggregate<A> {
+    fn borrow(&self) -> &A {
+        &self.state
+    }
+}
+
+/// An identified, specific instance of a hydrated aggregate.
+pub struct Entity<I, A> {
+    id: I,
+    aggregate: HydratedAggregate<A>,
+}
+
+impl<I, A> Entity<I, A> {
+    /// Creates a new entity from an identifier and an associated hydrated aggregate.
+    pub fn new(id: I, aggregate: HydratedAggregate<A>) -> Self {
+        Entity { id, aggregate }
+    }
+
+    /// The entity's identifier.
+    pub fn id(&self) -> &I {
+        &self.id
+    }
+
+    /// An immutable reference to the underlying aggregate.
+    pub fn aggregate(&self) -> &HydratedAggregate<A> {
+        &self.aggregate
+    }
+
+    /// A mutable reference to the underlying aggregate.
+    pub fn aggregate_mut(&mut self) -> &mut HydratedAggregate<A> {
+        &mut self.aggregate
+    }
+}
+
+impl<I, A> Entity<I, A>
+where
+    I: AggregateId<A>,
+    A: Aggregate,
+{
+    pub fn identifier_str(&self) -> &str {
+        self.id.as_str()
+    }
+}
+
+impl<I, A> From<Entity<I, A>> for HydratedAggregate<A> {
+    fn from(entity: Entity<I, A>) -> Self {
+        entity.aggregate
+    }
+}
+
+impl<I, A> AsRef<HydratedAggregate<A>> for Entity<I, A> {
+    fn as_ref(&self) -> &HydratedAggregate<A> {
+        &self.aggregate
+    }
+}
+
+impl<I, A> AsMut<HydratedAggregate<A>> for Entity<I, A> {
+    fn as_mut(&mut self) -> &mut HydratedAggregate<A> {
+        &mut self.aggregate
+    }
+}
+
+impl<I, A> Borrow<HydratedAggregate<A>> for Entity<I, A> {
+    fn borrow(&self) -> &HydratedAggregate<A> {
+        &self.aggregate
+    }
+}
+
+impl<I, A> BorrowMut<HydratedAggregate<A>> for Entity<I, A> {
+    fn borrow_mut(&mut self) -> &mut HydratedAggregate<A> {
+        &mut self.aggregate
+    }
+}
+
+// We can also let `Entity<I, A>` borrow as `A` if we like:
+impl<I, A> Borrow<A> for Entity<I, A> {
+    fn borrow(&self) -> &A {
+        self.aggregate.borrow()
+    }
+}
+
+/// A concrete type that implements `Aggregate`.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MyAggregate {
+    pub count: i64,
+}
+
+impl Aggregate for MyAggregate {
+    fn aggregate_type() -> &'static str {
+        "MyAggregate"
+    }
+}
+
+/// A simple `Event` type for demonstration.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum MyEvent {
+    /// Since the schema version 2, it has an optional reason.
+    Increment {
+        by: i64,
+        reason: Option<String>,
+    },
+    Decrement(i64),
+}
+
+impl MyEvent {
+    /// Creates an `Increment` event without a reason.
+    pub fn increment(by: i64) -> Self {
+        MyEvent::Increment { by, reason: None }
+    }
+
+    /// Upcasters of `MyEvent`s stored with outdated schemas.
+    pub fn upcasters() -> Upcasters {
+        Upcasters::new().with("Increment", 1, |mut payload| {
+            let by = payload
+                .as_object_mut()
+                .and_then(|variant| variant.get_mut("Increment"))
+                .map(Value::take)
+                .ok_or_else(|| "expected an `Increment` variant".to_owned())?;
+            Ok(json!({ "Increment": { "by": by, "reason": null } }))
+        })
+    }
+}
+
+impl Event for MyEvent {
+    fn event_type(&self) -> &'static str {
+        match *self {
+            MyEvent::Increment { .. } => "Increment",
+            MyEvent::Decrement(_) => "Decrement",
+        }
+    }
+
+    fn schema_version(&self) -> u32 {
+        match *self {
+            MyEvent::Increment { .. } => 2,
+            MyEvent::Decrement(_) => 1,
+        }
+    }
+}
+
+/// Applying our `MyEvent` to `MyAggregate`.
+impl AggregateEvent<MyAggregate> for MyEvent {
+    fn apply_to(self, aggregate: &mut MyAggregate) {
+        match self {
+            MyEvent::Increment { by, .. } => {
+                aggregate.count += by;
+            }
+            MyEvent::Decrement(x) => {
+                aggregate.count -= x;
+            }
+        }
+    }
+}
+
+/// A simple command for `MyAggregate`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MyCommand {
+    Increment(i64),
+    Decrement(i64),
+}
+
+/// The reason of `MyAggregate` rejecting a `MyCommand`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MyError {
+    /// The count would become negative.
+    NegativeCount(i64),
+    /// The count would overflow.
+    Overflow,
+}
+
+impl fmt::Display for MyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MyError::NegativeCount(count) => write!(f, "count would become negative: {count}"),
+            MyError::Overflow => f.write_str("count would overflow"),
+        }
+    }
+}
+
+impl error::Error for MyError {}
+
+/// Deciding on `MyEvent`s, keeping the count of `MyAggregate` non-negative.
+impl CommandHandler for MyAggregate {
+    type Command = MyCommand;
+    type Event = MyEvent;
+    type Error = MyError;
+
+    fn handle(&self, cmd: &MyCommand) -> Result<Vec<MyEvent>, MyError> {
+        let (count, event) = match *cmd {
+            MyCommand::Increment(x) => (self.count.checked_add(x), MyEvent::increment(x)),
+            MyCommand::Decrement(x) => (self.count.checked_sub(x), MyEvent::Decrement(x)),
+        };
+        match count {
+            None => Err(MyError::Overflow),
+            Some(count) if count < 0 => Err(MyError::NegativeCount(count)),
+            Some(_) => Ok(vec![event]),
+        }
+    }
+}
+
+/// Example of an ID type for `MyAggregate`.
+#[derive(Debug, Clone)]
+pub struct MyAggregateId(pub String);
+
+impl AggregateId<MyAggregate> for MyAggregateId {
+    fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// A read model of the counts of all the `MyAggregate`s.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MyCounts {
+    counts: BTreeMap<String, i64>,
+    total: i64,
+}
+
+impl MyCounts {
+    /// The count of the aggregate identified by `id`.
+    pub fn count(&self, id: &str) -> Option<i64> {
+        self.counts.get(id).copied()
+    }
+
+    /// The sum of the counts of all the aggregates.
+    pub fn total(&self) -> i64 {
+        self.total
+    }
+}
+
+impl Projection for MyCounts {
+    type Aggregate = MyAggregate;
+    type Event = MyEvent;
+
+    fn project(&mut self, id: &str, _: EventNumber, event: MyEvent) {
+        let delta = match event {
+            MyEvent::Increment { by, .. } => by,
+            MyEvent::Decrement(x) => -x,
+        };
+        *self.counts.entry(id.to_owned()).or_default() += delta;
+        self.total += delta;
+    }
+
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_increments() {
+        let mut agg = HydratedAggregate::<MyAggregate>::default();
+        assert_eq!(agg.version(), Version::Initial);
+
+        // Apply single event
+        agg.apply(MyEvent::increment(10));
+        match agg.version() {
+            Version::Number(n) => assert_eq!(n.0.get(), 1),
+            _ => panic!("Expected version to be Number(1) after first event"),
+        }
+
+        // Apply multiple events
+        agg.apply_events(vec![MyEvent::increment(5), MyEvent::Decrement(3)]);
+        match agg.version() {
+            Version::Number(n) => assert_eq!(n.0.get(), 3),
+            _ => panic!("Expected version to be Number(3) after three events"),
+        }
+    }
+
+    #[test]
+    fn test_increments_never_overflow() {
+        let mut max = EventNumber::MAX_VALUE;
+        assert_eq!(max.next(), Err(VersionOverflow));
+        assert_eq!(max.incr(), Err(VersionOverflow));
+        max.saturating_incr();
+        assert_eq!(max, EventNumber::MAX_VALUE);
+        assert_eq!(EventNumber::MIN_VALUE.next().map(EventNumber::get), Ok(2));
+
+        let mut version = Version::Number(EventNumber::MAX_VALUE);
+        assert_eq!(version.next(), Err(VersionOverflow));
+        assert_eq!(version.incr(), Err(VersionOverflow));
+        version.saturating_incr();
+        assert_eq!(version.as_u64(), u64::MAX);
+
+        let mut version = Version::Initial;
+        assert_eq!(version.next(), Ok(Version::new(1)));
+        assert_eq!(version.incr(), Ok(()));
+        assert_eq!(version.as_u64(), 1);
+        version.saturating_incr();
+        assert_eq!(version, Version::new(2));
+    }
+
+    #[test]
+    fn test_version_gaps() {
+        let (initial, five, seven) = (Version::Initial, Version::new(5), Version::new(7));
+
+        assert_eq!(seven - five, 2);
+        assert_eq!(five - initial, 5);
+        assert_eq!(seven.checked_sub(five), Some(2));
+        assert_eq!(five.checked_sub(seven), None);
+        assert_eq!(initial.as_u64(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "version 7 is newer than 5")]
+    fn test_version_gap_to_newer_panics() {
+        let _ = Version::new(5) - Version::new(7);
+    }
+
+    #[test]
+    fn test_version_formats() {
+        assert_eq!(Version::Initial.to_string(), "0");
+        assert_eq!(Version::new(42).to_string(), "42");
+        assert_eq!(EventNumber::MIN_VALUE.to_string(), "1");
+
+        assert_eq!(serde_json::to_string(&Version::Initial).unwrap(), "0");
+        assert_eq!(serde_json::to_string(&Version::new(3)).unwrap(), "3");
+        assert_eq!(
+            serde_json::from_str::<Version>("3").unwrap(),
+            Version::new(3)
+        );
+        assert_eq!(
+            serde_json::from_str::<Version>("0").unwrap(),
+            Version::Initial
+        );
+        assert_eq!(serde_json::to_string(&EventNumber::MIN_VALUE).unwrap(), "1");
+        assert!(serde_json::from_str::<EventNumber>("0").is_err());
+    }
+
+    #[test]
+    fn test_aggregate_state_changes() {
+        let mut agg = HydratedAggregate::<MyAggregate>::default();
+        assert_eq!(agg.state().count, 0);
+
+        agg.apply(MyEvent::increment(10));
+        assert_eq!(agg.state().count, 10);
+
+        agg.apply_events(vec![MyEvent::increment(5), MyEvent::Decrement(2)]);
+        assert_eq!(agg.state().count, 13);
+    }
+
+    #[test]
+    fn test_entity_id() {
+        let id = MyAggregateId("abc123".to_owned());
+        let agg = HydratedAggregate::<MyAggregate>::default();
+        let entity = Entity::new(id, agg);
+        assert_eq!(entity.identifier_str(), "abc123");
+    }
+
+    #[test]
+    fn test_entity_versions() {
+        let id = MyAggregateId("xyz".to_owned());
+        let mut entity = Entity::new(id, HydratedAggregate::<MyAggregate>::default());
+
+        assert_eq!(entity.aggregate().version(), Version::Initial);
+        entity.aggregate_mut().apply(MyEvent::increment(2));
+
+        match entity.aggregate().version() {
+            Version::Number(n) => assert_eq!(n.0.get(), 1),
+            _ => panic!("Expected version to be Number(1)"),
+        }
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Aggregate, AggregateEvent, EventNumber, EventStore, StoreError, Version};
+
+/// A query-optimized view maintained from the events of aggregates.
+pub trait Projection {
+    /// The type of the aggregates the events are projected from.
+    type Aggregate: Aggregate;
+
+    /// The type of the projected events.
+    type Event: AggregateEvent<Self::Aggregate>;
+
+    /// Applies the event numbered `number` of the aggregate identified by `id` to the view.
+    ///
+    /// Events of each aggregate are projected in order, but events of different aggregates
+    /// may be interleaved arbitrarily.
+    fn project(&mut self, id: &str, number: EventNumber, event: Self::Event);
+
+    /// Clears the view, so it can be rebuilt from scratch.
+    fn reset(&mut self);
+}
+
+/// The number of the last event projected, by aggregate ids.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Checkpoints(HashMap<String, EventNumber>);
+
+impl Checkpoints {
+    /// The number of the last projected event of the aggregate identified by `id`, if any.
+    pub fn get(&self, id: &str) -> Option<EventNumber> {
+        self.0.get(id).copied()
+    }
+
+    /// The version of the aggregate identified by `id` the projection is up to.
+    fn version(&self, id: &str) -> Version {
+        self.get(id).map_or(Version::Initial, Version::Number)
+    }
+}
+
+/// Feeder of a [`Projection`] with the events from an [`EventStore`].
+///
+/// The [`Checkpoints`] are advanced along with the projection, so persisting them together
+/// with the view allows resuming the projection after a restart.
+#[derive(Debug)]
+pub struct Projector<P> {
+    projection: P,
+    checkpoints: Checkpoints,
+}
+
+impl<P: Projection> Projector<P> {
+    /// Creates a projector for the empty `projection`.
+    pub fn new(projection: P) -> Self {
+        Self::resume(projection, Checkpoints::default())
+    }
+
+    /// Creates a projector for the `projection` which is up to the `checkpoints` already.
+    pub fn resume(projection: P, checkpoints: Checkpoints) -> Self {
+        Self {
+            projection,
+            checkpoints,
+        }
+    }
+
+    /// The maintained projection.
+    pub fn projection(&self) -> &P {
+        &self.projection
+    }
+
+    /// The events projected so far.
+    pub fn checkpoints(&self) -> &Checkpoints {
+        &self.checkpoints
+    }
+
+    /// Splits the projector into the projection and its checkpoints.
+    pub fn into_parts(self) -> (P, Checkpoints) {
+        (self.projection, self.checkpoints)
+    }
+
+    /// Projects all the events of the `store` following the checkpoints, returning the number
+    /// of the projected events.
+    pub fn catch_up<S>(&mut self, store: &S) -> Result<usize, StoreError>
+    where
+        S: EventStore<P::Aggregate, P::Event>,
+    {
+        let mut projected = 0;
+        for id in store.ids()? {
+            let events = store.read(&id, self.checkpoints.version(&id))?;
+            let Some(&(last, _)) = events.last() else {
+                continue;
+            };
+            projected += events.len();
+            for (number, event) in events {
+                self.projection.project(&id, number, event);
+            }
+            self.checkpoints.0.insert(id, last);
+        }
+        Ok(projected)
+    }
+
+    /// Resets the projection and projects all the events of the `store` from scratch.
+    pub fn rebuild<S>(&mut self, store: &S) -> Result<usize, StoreError>
+    where
+        S: EventStore<P::Aggregate, P::Event>,
+    {
+        self.projection.reset();
+        self.checkpoints.0.clear();
+        self.catch_up(store)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FileEventStore, MyAggregate, MyAggregateId, MyCounts, MyEvent};
+
+    fn append(store: &FileEventStore, id: &str, events: &[MyEvent]) {
+        let entity =
+            EventStore::<MyAggregate, MyEvent>::load(store, MyAggregateId(id.to_owned())).unwrap();
+        EventStore::<MyAggregate, _>::append(store, id, entity.aggregate().version(), events)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_catch_up_projects_new_events_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileEventStore::open(dir.path()).unwrap();
+        append(&store, "a", &[MyEvent::increment(5), MyEvent::Decrement(2)]);
+        append(&store, "b", &[MyEvent::increment(10)]);
+
+        let mut projector = Projector::new(MyCounts::default());
+        assert_eq!(projector.catch_up(&store).unwrap(), 3);
+        assert_eq!(projector.projection().count("a"), Some(3));
+        assert_eq!(projector.projection().total(), 13);
+        assert_eq!(projector.checkpoints().get("a"), EventNumber::new(2));
+
+        assert_eq!(projector.catch_up(&store).unwrap(), 0);
+        append(&store, "a", &[MyEvent::increment(1)]);
+        append(&store, "c", &[MyEvent::Decrement(4)]);
+        assert_eq!(projector.catch_up(&store).unwrap(), 2);
+        assert_eq!(projector.projection().count("a"), Some(4));
+        assert_eq!(projector.projection().count("c"), Some(-4));
+        assert_eq!(projector.projection().total(), 10);
+    }
+
+    #[test]
+    fn test_resume_after_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileEventStore::open(dir.path()).unwrap();
+        append(&store, "a", &[MyEvent::increment(5)]);
+
+        let mut projector = Projector::new(MyCounts::default());
+        projector.catch_up(&store).unwrap();
+        let saved = serde_json::to_string(&projector.into_parts()).unwrap();
+
+        append(&store, "a", &[MyEvent::increment(1)]);
+        append(&store, "b", &[MyEvent::increment(2)]);
+        let (projection, checkpoints) = serde_json::from_str(&saved).unwrap();
+        let mut projector = Projector::<MyCounts>::resume(projection, checkpoints);
+        assert_eq!(projector.catch_up(&store).unwrap(), 2);
+        assert_eq!(projector.projection().count("a"), Some(6));
+        assert_eq!(projector.projection().total(), 8);
+    }
+
+    #[test]
+    fn test_rebuild_from_scratch() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileEventStore::open(dir.path()).unwrap();
+        append(&store, "a", &[MyEvent::increment(5), MyEvent::increment(2)]);
+        append(&store, "b", &[MyEvent::Decrement(1)]);
+
+        let mut projector = Projector::new(MyCounts::default());
+        projector.catch_up(&store).unwrap();
+        let caught_up = projector.projection().clone();
+
+        assert_eq!(projector.rebuild(&store).unwrap(), 3);
+        assert_eq!(projector.projection(), &caught_up);
+        assert_eq!(projector.checkpoints().get("b"), EventNumber::new(1));
+    }
+}
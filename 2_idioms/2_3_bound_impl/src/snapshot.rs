@@ -0,0 +1,504 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Write as _};
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::event_store::escape;
+use crate::{
+    Aggregate, AggregateEvent, AggregateId, Entity, EventNumber, EventStore, HydratedAggregate,
+    StoreError, Version,
+};
+
+/// A state of an aggregate captured at some version.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot<S> {
+    /// The version of the aggregate the state corresponds to.
+    pub version: Version,
+    /// When the snapshot was taken.
+    pub taken_at: SystemTime,
+    /// The captured state of the aggregate.
+    pub state: S,
+}
+
+/// A persistent storage of the latest snapshot of each aggregate.
+pub trait SnapshotStore<A> {
+    /// Stores the `snapshot` of the aggregate identified by `id`, replacing the previous one.
+    fn save(&self, id: &str, snapshot: Snapshot<&A>) -> Result<(), StoreError>;
+
+    /// Loads the latest snapshot of the aggregate identified by `id`, if any.
+    fn load(&self, id: &str) -> Result<Option<Snapshot<A>>, StoreError>;
+}
+
+/// A [`SnapshotStore`] keeping each snapshot in a JSON file.
+///
+/// Snapshots are stored as `<dir>/<aggregate type>/<id>.json` and replaced atomically, so a
+/// crash never leaves a torn snapshot behind.
+#[derive(Debug)]
+pub struct FileSnapshotStore {
+    dir: PathBuf,
+}
+
+/// A stored snapshot, as the contents of a snapshot file.
+#[derive(Serialize, Deserialize)]
+struct Record<S> {
+    version: Version,
+    taken_at: SystemTime,
+    state: S,
+}
+
+impl FileSnapshotStore {
+    /// Opens the store in the `dir`, creating it if it doesn't exist.
+    pub fn open(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// The directory the snapshots are stored in.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn snapshot_path<A: Aggregate>(&self, id: &str) -> PathBuf {
+        self.dir
+            .join(A::aggregate_type())
+            .join(format!("{}.json", escape(id)))
+    }
+}
+
+impl<A> SnapshotStore<A> for FileSnapshotStore
+where
+    A: Aggregate + Serialize + DeserializeOwned,
+{
+    fn save(&self, id: &str, snapshot: Snapshot<&A>) -> Result<(), StoreError> {
+        let path = self.snapshot_path::<A>(id);
+        let record = Record {
+            version: snapshot.version,
+            taken_at: snapshot.taken_at,
+            state: snapshot.state,
+        };
+        let contents = serde_json::to_vec(&record)?;
+
+        fs::create_dir_all(path.parent().expect("snapshot path has a parent"))?;
+        let tmp = path.with_extension("json.tmp");
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(&contents)?;
+        file.sync_data()?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    }
+
+    fn load(&self, id: &str) -> Result<Option<Snapshot<A>>, StoreError> {
+        let contents = match fs::read(self.snapshot_path::<A>(id)) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        let record: Record<A> = serde_json::from_slice(&contents)?;
+        Ok(Some(Snapshot {
+            version: record.version,
+            taken_at: record.taken_at,
+            state: record.state,
+        }))
+    }
+}
+
+/// A rule deciding when a new snapshot of an aggregate is due.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotPolicy {
+    /// Snapshots are never taken.
+    Never,
+    /// A snapshot is taken once at least the given number of events were applied since the
+    /// last one.
+    EveryEvents(u64),
+    /// A snapshot of a changed aggregate is taken once the given time has elapsed since the
+    /// last one.
+    Elapsed(Duration),
+}
+
+impl SnapshotPolicy {
+    /// Checks whether a snapshot is due, given the number of events applied and the time
+    /// elapsed since the last snapshot, if there is one.
+    pub fn is_due(&self, events_since: u64, elapsed: Option<Duration>) -> bool {
+        match *self {
+            Self::Never => false,
+            Self::EveryEvents(count) => events_since > 0 && events_since >= count,
+            Self::Elapsed(period) => events_since > 0 && elapsed.is_none_or(|e| e >= period),
+        }
+    }
+}
+
+/// An [`EventStore`] combined with a [`SnapshotStore`], taking snapshots according to a
+/// [`SnapshotPolicy`].
+pub struct SnapshotRepository<A, E, ES, SS> {
+    events: ES,
+    snapshots: SS,
+    policy: SnapshotPolicy,
+    /// When the snapshots seen by this repository were taken, by aggregate ids.
+    taken_at: Mutex<HashMap<String, SystemTime>>,
+    _types: PhantomData<fn(E) -> A>,
+}
+
+impl<A, E, ES, SS> SnapshotRepository<A, E, ES, SS>
+where
+    A: Aggregate,
+    E: AggregateEvent<A>,
+    ES: EventStore<A, E>,
+    SS: SnapshotStore<A>,
+{
+    pub fn new(events: ES, snapshots: SS, policy: SnapshotPolicy) -> Self {
+        Self {
+            events,
+            snapshots,
+            policy,
+            taken_at: Mutex::new(HashMap::new()),
+            _types: PhantomData,
+        }
+    }
+
+    /// The underlying event store.
+    pub fn events(&self) -> &ES {
+        &self.events
+    }
+
+    /// The underlying snapshot store.
+    pub fn snapshots(&self) -> &SS {
+        &self.snapshots
+    }
+
+    /// Restores the aggregate identified by `id` from its latest snapshot, replaying only the
+    /// events following it.
+    ///
+    /// Returns nothing without a usable snapshot. An undecodable snapshot, e.g. of an outdated
+    /// state, isn't usable, nor is a snapshot ahead of the stream, e.g. restored from a newer
+    /// backup.
+    fn restore(&self, id: &str) -> Result<Option<HydratedAggregate<A>>, StoreError> {
+        let snapshot = match self.snapshots.load(id) {
+            Ok(Some(snapshot)) => snapshot,
+            Ok(None) | Err(StoreError::Serde(_)) => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let mut aggregate = HydratedAggregate::from_snapshot(snapshot.version, snapshot.state);
+        if let Version::Number(number) = snapshot.version {
+            // The event the snapshot was taken at is read too, to check the stream reaches it.
+            let mut events = self
+                .events
+                .read(id, Version::new(number.get() - 1))?
+                .into_iter();
+            if events.next().is_none_or(|(first, _)| first != number) {
+                return Ok(None);
+            }
+            aggregate.apply_events(events.map(|(_, event)| event));
+        }
+        self.taken_at
+            .lock()
+            .unwrap()
+            .insert(id.to_owned(), snapshot.taken_at);
+        Ok(Some(aggregate))
+    }
+}
+
+/// Events are read and appended through the underlying event store, while aggregates are
+/// loaded from their latest snapshots, and snapshotted on commits.
+impl<A, E, ES, SS> EventStore<A, E> for SnapshotRepository<A, E, ES, SS>
+where
+    A: Aggregate,
+    E: AggregateEvent<A>,
+    ES: EventStore<A, E>,
+    SS: SnapshotStore<A>,
+{
+    fn append(&self, id: &str, expected: Version, events: &[E]) -> Result<Version, StoreError> {
+        self.events.append(id, expected, events)
+    }
+
+    fn read(&self, id: &str, after: Version) -> Result<Vec<(EventNumber, E)>, StoreError> {
+        self.events.read(id, after)
+    }
+
+    fn ids(&self) -> Result<Vec<String>, StoreError> {
+        self.events.ids()
+    }
+
+    /// Loads the aggregate identified by `id` from its latest snapshot, replaying only the
+    /// events following it.
+    ///
+    /// Falls back to replaying all the events if the snapshot is missing or unusable.
+    fn load<I>(&self, id: I) -> Result<Entity<I, A>, StoreError>
+    where
+        I: AggregateId<A>,
+    {
+        match self.restore(id.as_str())? {
+            Some(aggregate) => Ok(Entity::new(id, aggregate)),
+            None => self.events.load(id),
+        }
+    }
+
+    /// Appends the `events` to the stream of the `entity` and applies them to it, taking a
+    /// snapshot if one is due.
+    ///
+    /// Fails with [`StoreError::Conflict`] if the `entity` is stale, leaving it untouched.
+    /// Failing to take a snapshot isn't an error, as the events are stored already, and it's
+    /// retried on the next commit.
+    fn commit<I>(&self, entity: &mut Entity<I, A>, events: Vec<E>) -> Result<Version, StoreError>
+    where
+        I: AggregateId<A>,
+    {
+        let id = entity.identifier_str().to_owned();
+        let aggregate = entity.aggregate_mut();
+        let version = self.events.append(&id, aggregate.version(), &events)?;
+        aggregate.apply_events(events);
+
+        // A snapshot ahead of the stream is stale, so the events are counted from scratch.
+        let events_since = aggregate
+            .snapshot_version()
+            .and_then(|snapshot_version| version.checked_sub(snapshot_version))
+            .unwrap_or(version.as_u64());
+        let now = SystemTime::now();
+        let mut taken_at = self.taken_at.lock().unwrap();
+        let elapsed = taken_at
+            .get(&id)
+            .map(|at| now.duration_since(*at).unwrap_or_default());
+        if self.policy.is_due(events_since, elapsed) {
+            let snapshot = Snapshot {
+                version,
+                taken_at: now,
+                state: aggregate.state(),
+            };
+            if self.snapshots.save(&id, snapshot).is_ok() {
+                aggregate.set_snapshot_version(version);
+                taken_at.insert(id, now);
+            }
+        }
+        Ok(version)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FileEventStore, MyAggregate, MyAggregateId, MyEvent};
+
+    type Repository<ES = FileEventStore> =
+        SnapshotRepository<MyAggregate, MyEvent, ES, FileSnapshotStore>;
+
+    fn repository<ES>(dir: &Path, events: ES, policy: SnapshotPolicy) -> Repository<ES>
+    where
+        ES: EventStore<MyAggregate, MyEvent>,
+    {
+        let snapshots = FileSnapshotStore::open(dir.join("snapshots")).unwrap();
+        SnapshotRepository::new(events, snapshots, policy)
+    }
+
+    fn id(id: &str) -> MyAggregateId {
+        MyAggregateId(id.to_owned())
+    }
+
+    // Deterministic, but irregular sequence of events.
+    fn event(i: i64) -> MyEvent {
+        if i % 3 == 0 {
+            MyEvent::Decrement(i * 7 % 11)
+        } else {
+            MyEvent::increment(i * 5 % 13)
+        }
+    }
+
+    // Event store recording the versions it was read after.
+    struct Recording {
+        inner: FileEventStore,
+        reads: Mutex<Vec<Version>>,
+    }
+
+    impl EventStore<MyAggregate, MyEvent> for Recording {
+        fn append(
+            &self,
+            id: &str,
+            expected: Version,
+            events: &[MyEvent],
+        ) -> Result<Version, StoreError> {
+            EventStore::<MyAggregate, _>::append(&self.inner, id, expected, events)
+        }
+
+        fn read(
+            &self,
+            id: &str,
+            after: Version,
+        ) -> Result<Vec<(EventNumber, MyEvent)>, StoreError> {
+            self.reads.lock().unwrap().push(after);
+            EventStore::<MyAggregate, _>::read(&self.inner, id, after)
+        }
+
+        fn ids(&self) -> Result<Vec<String>, StoreError> {
+            EventStore::<MyAggregate, MyEvent>::ids(&self.inner)
+        }
+    }
+
+    #[test]
+    fn test_policy() {
+        let every = SnapshotPolicy::EveryEvents(3);
+        assert!(!every.is_due(2, None));
+        assert!(every.is_due(3, None));
+        assert!(every.is_due(4, Some(Duration::ZERO)));
+
+        let hourly = SnapshotPolicy::Elapsed(Duration::from_secs(3600));
+        assert!(hourly.is_due(1, None));
+        assert!(!hourly.is_due(0, None));
+        assert!(!hourly.is_due(100, Some(Duration::from_secs(60))));
+        assert!(hourly.is_due(1, Some(Duration::from_secs(3600))));
+
+        assert!(!SnapshotPolicy::Never.is_due(100, None));
+    }
+
+    #[test]
+    fn test_file_store_keeps_latest() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileSnapshotStore::open(dir.path()).unwrap();
+        assert_eq!(
+            SnapshotStore::<MyAggregate>::load(&store, "a").unwrap(),
+            None
+        );
+
+        let taken_at = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000);
+        for count in [1, 2] {
+            let snapshot = Snapshot {
+                version: Version::new(count as u64),
+                taken_at,
+                state: &MyAggregate { count },
+            };
+            store.save("a", snapshot).unwrap();
+        }
+        let expected = Snapshot {
+            version: Version::new(2),
+            taken_at,
+            state: MyAggregate { count: 2 },
+        };
+        assert_eq!(store.load("a").unwrap(), Some(expected));
+        assert_eq!(
+            SnapshotStore::<MyAggregate>::load(&store, "b").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_state_equals_full_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let events = FileEventStore::open(dir.path().join("events")).unwrap();
+        let repo = repository(dir.path(), events, SnapshotPolicy::EveryEvents(4));
+
+        let mut entity = repo.load(id("a")).unwrap();
+        for i in 0..25 {
+            // Batches of varying size, so snapshots don't always fall on multiples of 4.
+            let batch = (0..i % 3 + 1).map(|j| event(i * 3 + j)).collect();
+            repo.commit(&mut entity, batch).unwrap();
+        }
+
+        let snapshotted = repo.load(id("a")).unwrap();
+        let replayed = EventStore::<_, MyEvent>::load(repo.events(), id("a")).unwrap();
+        assert!(snapshotted.aggregate().snapshot_version().is_some());
+        assert_eq!(replayed.aggregate().snapshot_version(), None);
+        assert_eq!(
+            snapshotted.aggregate().state(),
+            replayed.aggregate().state()
+        );
+        assert_eq!(snapshotted.aggregate().state(), entity.aggregate().state());
+        assert_eq!(
+            snapshotted.aggregate().version(),
+            replayed.aggregate().version()
+        );
+    }
+
+    #[test]
+    fn test_load_replays_only_later_events() {
+        let dir = tempfile::tempdir().unwrap();
+        let events = Recording {
+            inner: FileEventStore::open(dir.path().join("events")).unwrap(),
+            reads: Mutex::new(Vec::new()),
+        };
+        let repo = repository(dir.path(), events, SnapshotPolicy::EveryEvents(5));
+
+        let mut entity = repo.load(id("a")).unwrap();
+        for i in 0..7 {
+            repo.commit(&mut entity, vec![event(i)]).unwrap();
+        }
+        assert_eq!(entity.aggregate().snapshot_version(), Some(Version::new(5)));
+
+        let loaded = repo.load(id("a")).unwrap();
+        assert_eq!(loaded.aggregate().snapshot_version(), Some(Version::new(5)));
+        assert_eq!(loaded.aggregate().version(), Version::new(7));
+        assert_eq!(loaded.aggregate().state(), entity.aggregate().state());
+        let reads = repo.events().reads.lock().unwrap();
+        assert_eq!(*reads, [Version::Initial, Version::new(4)]);
+    }
+
+    #[test]
+    fn test_elapsed_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let events = FileEventStore::open(dir.path().join("events")).unwrap();
+        let policy = SnapshotPolicy::Elapsed(Duration::from_secs(3600));
+        let repo = repository(dir.path(), events, policy);
+
+        let mut entity = repo.load(id("a")).unwrap();
+        repo.commit(&mut entity, vec![event(1)]).unwrap();
+        repo.commit(&mut entity, vec![event(2)]).unwrap();
+        assert_eq!(entity.aggregate().snapshot_version(), Some(Version::new(1)));
+    }
+
+    #[test]
+    fn test_stale_entity_is_untouched() {
+        let dir = tempfile::tempdir().unwrap();
+        let events = FileEventStore::open(dir.path().join("events")).unwrap();
+        let repo = repository(dir.path(), events, SnapshotPolicy::EveryEvents(1));
+
+        let mut stale = repo.load(id("a")).unwrap();
+        let mut entity = repo.load(id("a")).unwrap();
+        repo.commit(&mut entity, vec![event(1)]).unwrap();
+
+        let res = repo.commit(&mut stale, vec![event(2)]);
+        assert!(matches!(res, Err(StoreError::Conflict { .. })));
+        assert_eq!(stale.aggregate().version(), Version::Initial);
+        assert_eq!(stale.aggregate().snapshot_version(), None);
+    }
+
+    #[test]
+    fn test_undecodable_snapshot_is_replayed() {
+        let dir = tempfile::tempdir().unwrap();
+        let events = FileEventStore::open(dir.path().join("events")).unwrap();
+        let repo = repository(dir.path(), events, SnapshotPolicy::EveryEvents(2));
+        let mut entity = repo.load(id("a")).unwrap();
+        for i in 0..3 {
+            repo.commit(&mut entity, vec![event(i)]).unwrap();
+        }
+
+        let path = repo.snapshots().snapshot_path::<MyAggregate>("a");
+        fs::write(&path, r#"{"version":2,"state":"outdated"}"#).unwrap();
+        let loaded = repo.load(id("a")).unwrap();
+        assert_eq!(loaded.aggregate().snapshot_version(), None);
+        assert_eq!(loaded.aggregate().version(), Version::new(3));
+        assert_eq!(loaded.aggregate().state(), entity.aggregate().state());
+    }
+
+    #[test]
+    fn test_snapshot_ahead_of_stream_is_ignored() {
+        let dir = tempfile::tempdir().unwrap();
+        let events = FileEventStore::open(dir.path().join("events")).unwrap();
+        let repo = repository(dir.path(), events, SnapshotPolicy::Never);
+        let mut entity = repo.load(id("a")).unwrap();
+        repo.commit(&mut entity, vec![event(1), event(2)]).unwrap();
+
+        let snapshot = Snapshot {
+            version: Version::new(5),
+            taken_at: SystemTime::now(),
+            state: &MyAggregate { count: 100 },
+        };
+        repo.snapshots().save("a", snapshot).unwrap();
+        let loaded = repo.load(id("a")).unwrap();
+        assert_eq!(loaded.aggregate().snapshot_version(), None);
+        assert_eq!(loaded.aggregate().version(), Version::new(2));
+        assert_eq!(loaded.aggregate().state(), entity.aggregate().state());
+    }
+}
@@ -0,0 +1,133 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt;
+
+use serde_json::Value;
+
+/// A transformation of a serialized event payload to the next schema version, failing with
+/// the reason if the payload is malformed.
+type Upcast = Box<dyn Fn(Value) -> Result<Value, String> + Send + Sync>;
+
+/// A chain of upcasters, transforming serialized payloads of events stored with outdated
+/// schemas to their current schemas.
+///
+/// Upcasters are identified by event types only, so these must be unique among all the events
+/// sharing the chain.
+#[derive(Default)]
+pub struct Upcasters {
+    /// Upcasters by event types and the schema versions they upcast from.
+    steps: HashMap<(Cow<'static, str>, u32), Upcast>,
+}
+
+impl Upcasters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the `upcast` of `event_type` payloads from the schema `version` to the next one.
+    ///
+    /// # Panics
+    ///
+    /// If an upcaster from the same `version` of the `event_type` is registered already.
+    pub fn with<F>(mut self, event_type: &'static str, version: u32, upcast: F) -> Self
+    where
+        F: Fn(Value) -> Result<Value, String> + Send + Sync + 'static,
+    {
+        let key = (Cow::Borrowed(event_type), version);
+        assert!(
+            !self.steps.contains_key(&key),
+            "upcaster of {event_type} v{version} is registered already",
+        );
+        self.steps.insert(key, Box::new(upcast));
+        self
+    }
+
+    /// Upcasts the `payload` of an `event_type` event from the schema `version` through all the
+    /// registered upcasters, returning it along with the version it's upcast to.
+    ///
+    /// Fails with the reason if any of the upcasters rejects the payload as malformed.
+    pub fn upcast(
+        &self,
+        event_type: &str,
+        mut version: u32,
+        mut payload: Value,
+    ) -> Result<(u32, Value), String> {
+        let mut key = (Cow::Owned(event_type.to_owned()), version);
+        while let Some(upcast) = self.steps.get(&key) {
+            payload = upcast(payload)
+                .map_err(|reason| format!("upcasting {event_type} v{version}: {reason}"))?;
+            version += 1;
+            key.1 = version;
+        }
+        Ok((version, payload))
+    }
+}
+
+impl fmt::Debug for Upcasters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut steps: Vec<_> = self.steps.keys().collect();
+        steps.sort_unstable();
+        f.debug_struct("Upcasters").field("steps", &steps).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::MyEvent;
+
+    #[test]
+    fn test_chain_is_applied_in_order() {
+        let upcasters = Upcasters::new()
+            .with("Renamed", 2, |v| Ok(json!({ "v3": v["v2"] })))
+            .with("Renamed", 1, |v| Ok(json!({ "v2": v["v1"] })))
+            .with("Other", 1, |_| Err("unsupported".to_owned()));
+
+        let (version, payload) = upcasters.upcast("Renamed", 1, json!({ "v1": 5 })).unwrap();
+        assert_eq!((version, payload), (3, json!({ "v3": 5 })));
+        let (version, payload) = upcasters.upcast("Renamed", 2, json!({ "v2": 5 })).unwrap();
+        assert_eq!((version, payload), (3, json!({ "v3": 5 })));
+        let (version, payload) = upcasters.upcast("Renamed", 3, json!({ "v3": 5 })).unwrap();
+        assert_eq!((version, payload), (3, json!({ "v3": 5 })));
+        assert_eq!(
+            upcasters.upcast("Other", 1, json!(null)),
+            Err("upcasting Other v1: unsupported".to_owned()),
+        );
+    }
+
+    #[test]
+    fn test_my_event_v1_is_upcast() {
+        let (version, payload) = MyEvent::upcasters()
+            .upcast("Increment", 1, json!({ "Increment": 5 }))
+            .unwrap();
+        assert_eq!(version, 2);
+        let event: MyEvent = serde_json::from_value(payload).unwrap();
+        assert_eq!(event, MyEvent::increment(5));
+
+        let current = serde_json::to_value(MyEvent::Decrement(3)).unwrap();
+        let (version, payload) = MyEvent::upcasters()
+            .upcast("Decrement", 1, current.clone())
+            .unwrap();
+        assert_eq!((version, payload), (1, current));
+    }
+
+    #[test]
+    fn test_malformed_my_event_v1_is_rejected() {
+        let upcasters = MyEvent::upcasters();
+        for malformed in [
+            json!(5),
+            json!({ "Decrement": 5 }),
+            json!([{ "Increment": 5 }]),
+        ] {
+            assert!(upcasters.upcast("Increment", 1, malformed).is_err());
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "upcaster of Increment v1 is registered already")]
+    fn test_duplicate_upcaster_panics() {
+        MyEvent::upcasters().with("Increment", 1, Ok);
+    }
+}